@@ -0,0 +1,112 @@
+use std::fmt;
+
+use game_session_io::{codec::Decode, GameSessionInit, SessionAction, SessionConfig, SessionEvent};
+use gtest::{Program, System};
+use wordle_io::WordleInit;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendError {
+    /// The program could not be deployed or found.
+    NotDeployed(String),
+    /// Game-session failed to process the message.
+    Failed,
+    NoReply,
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::NotDeployed(program) => write!(f, "{program} is not deployed"),
+            BackendError::Failed => write!(f, "game-session failed to process the message"),
+            BackendError::NoReply => write!(f, "no reply from game-session"),
+        }
+    }
+}
+
+/// Where game-session runs: an in-process gtest `System` for offline play, or a node.
+pub trait Backend {
+    /// Sends `action` to game-session from the player and returns every event the player
+    /// received while it was processed, the reply first.
+    fn send(&mut self, action: SessionAction) -> Result<Vec<SessionEvent>, BackendError>;
+
+    /// Lets `blocks` blocks pass, returns the events the player received meanwhile, e.g. a
+    /// `GameOver` for a game that timed out.
+    fn wait(&mut self, blocks: u32) -> Result<Vec<SessionEvent>, BackendError>;
+}
+
+const WORDLE_ID: u64 = 1;
+const GAME_SESSION_ID: u64 = 2;
+
+/// Deploys both programs in a fresh gtest `System`, with the player as their owner.
+pub struct GtestBackend {
+    system: System,
+    player: u64,
+}
+
+impl GtestBackend {
+    pub fn new(wasm_dir: &str, player: u64) -> Result<Self, BackendError> {
+        let system = System::new();
+        for program in ["wordle", "game_session"] {
+            let path = format!("{wasm_dir}/{program}.wasm");
+            if !std::path::Path::new(&path).exists() {
+                return Err(BackendError::NotDeployed(path));
+            }
+        }
+
+        let wordle = Program::from_file(&system, format!("{wasm_dir}/wordle.wasm"));
+        let game_session = Program::from_file(&system, format!("{wasm_dir}/game_session.wasm"));
+        if wordle
+            .send(
+                player,
+                WordleInit {
+                    session_programs: vec![GAME_SESSION_ID.into()],
+                },
+            )
+            .main_failed()
+        {
+            return Err(BackendError::NotDeployed("wordle".to_string()));
+        }
+        let init = GameSessionInit {
+            wordle_program: WORDLE_ID.into(),
+            config: SessionConfig::default(),
+            import: None,
+        };
+        if game_session.send(player, init).main_failed() {
+            return Err(BackendError::NotDeployed("game-session".to_string()));
+        }
+        Ok(Self { system, player })
+    }
+
+    fn player_events(&self, log: &[gtest::CoreLog]) -> Vec<SessionEvent> {
+        log.iter()
+            .filter(|log| log.destination() == self.player.into())
+            .filter_map(|log| SessionEvent::decode(&mut log.payload()).ok())
+            .collect()
+    }
+}
+
+impl Backend for GtestBackend {
+    fn send(&mut self, action: SessionAction) -> Result<Vec<SessionEvent>, BackendError> {
+        let game_session = self
+            .system
+            .get_program(GAME_SESSION_ID)
+            .ok_or(BackendError::NotDeployed("game-session".to_string()))?;
+        let result = game_session.send(self.player, action);
+        if result.main_failed() {
+            return Err(BackendError::Failed);
+        }
+        let events = self.player_events(result.log());
+        if events.is_empty() {
+            return Err(BackendError::NoReply);
+        }
+        Ok(events)
+    }
+
+    fn wait(&mut self, blocks: u32) -> Result<Vec<SessionEvent>, BackendError> {
+        let results = self.system.spend_blocks(blocks);
+        Ok(results
+            .iter()
+            .flat_map(|result| self.player_events(result.log()))
+            .collect())
+    }
+}
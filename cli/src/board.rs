@@ -0,0 +1,190 @@
+use game_session_io::{LetterState, SessionEvent, SessionResult};
+use wordle_io::{Alphabet, GameId, WORD_LENGTH};
+
+const CORRECT: &str = "\x1b[1;30;42m";
+const PRESENT: &str = "\x1b[1;30;43m";
+const ABSENT: &str = "\x1b[1;37;100m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub word: String,
+    /// `LetterState::Unknown` for the letters of a losing guess, which gets no feedback.
+    pub letters: Vec<LetterState>,
+}
+
+/// The player's view of the current session, kept up to date from game-session's events.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Board {
+    pub game_id: Option<GameId>,
+    pub alphabet: Option<Alphabet>,
+    pub rows: Vec<Row>,
+    pub keyboard: Vec<LetterState>,
+    /// Set once the game is over.
+    pub result: Option<SessionResult>,
+}
+
+impl Board {
+    pub fn is_playing(&self) -> bool {
+        self.alphabet.is_some() && self.result.is_none()
+    }
+
+    /// Applies an event received after sending `guess`, if it was a guess. Events of
+    /// other games than the one on the board, like the timeout of an earlier game, are ignored.
+    pub fn apply(&mut self, event: &SessionEvent, guess: Option<&str>) {
+        match event {
+            SessionEvent::GameStarted { game_id, alphabet } => {
+                *self = Board {
+                    game_id: Some(*game_id),
+                    alphabet: Some(*alphabet),
+                    keyboard: game_session_io::empty_keyboard(*alphabet),
+                    ..Board::default()
+                };
+            }
+            SessionEvent::WordChecked {
+                game_id,
+                correct_positions,
+                contained_in_word,
+                keyboard,
+            } if self.game_id == Some(*game_id) => {
+                if let Some(guess) = guess {
+                    let letters = (0..WORD_LENGTH as u8)
+                        .map(|i| {
+                            if correct_positions.contains(&i) {
+                                LetterState::Correct
+                            } else if contained_in_word.contains(&i) {
+                                LetterState::Present
+                            } else {
+                                LetterState::Absent
+                            }
+                        })
+                        .collect();
+                    self.rows.push(Row {
+                        word: guess.to_lowercase(),
+                        letters,
+                    });
+                }
+                self.keyboard = keyboard.clone();
+            }
+            SessionEvent::GameOver { game_id, result } if self.game_id == Some(*game_id) => {
+                if let Some(guess) = guess {
+                    // a winning guess is right everywhere, a losing one is not checked
+                    let state = if *result == SessionResult::Win {
+                        LetterState::Correct
+                    } else {
+                        LetterState::Unknown
+                    };
+                    self.rows.push(Row {
+                        word: guess.to_lowercase(),
+                        letters: vec![state; WORD_LENGTH],
+                    });
+                }
+                self.result = Some(result.clone());
+            }
+            _ => {}
+        }
+    }
+
+    pub fn render(&self, colour: bool) -> String {
+        let paint = |letter: char, state: LetterState| {
+            let letter = letter.to_uppercase().collect::<String>();
+            match (colour, state) {
+                (true, LetterState::Correct) => format!("{CORRECT} {letter} {RESET}"),
+                (true, LetterState::Present) => format!("{PRESENT} {letter} {RESET}"),
+                (true, LetterState::Absent) => format!("{ABSENT} {letter} {RESET}"),
+                (false, LetterState::Correct) => format!("[{letter}]"),
+                (false, LetterState::Present) => format!("({letter})"),
+                (false, LetterState::Absent) => format!(" {letter}-"),
+                (_, LetterState::Unknown) => format!(" {letter} "),
+            }
+        };
+
+        let mut out = String::new();
+        for row in &self.rows {
+            let line: Vec<String> = row
+                .word
+                .chars()
+                .zip(&row.letters)
+                .map(|(letter, state)| paint(letter, *state))
+                .collect();
+            out.push_str(&line.join(" "));
+            out.push('\n');
+        }
+        if let Some(alphabet) = self.alphabet {
+            let keys: Vec<String> = alphabet
+                .letters()
+                .iter()
+                .zip(&self.keyboard)
+                .map(|(letter, state)| paint(*letter, *state))
+                .collect();
+            out.push('\n');
+            out.push_str(&keys.join(""));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_a_game() {
+        let mut board = Board::default();
+        assert!(!board.is_playing());
+
+        board.apply(
+            &SessionEvent::GameStarted {
+                game_id: 3,
+                alphabet: Alphabet::Latin,
+            },
+            None,
+        );
+        assert!(board.is_playing());
+        assert_eq!(board.keyboard.len(), 26);
+
+        let mut keyboard = vec![LetterState::Unknown; 26];
+        keyboard[7] = LetterState::Correct;
+        let checked = SessionEvent::WordChecked {
+            game_id: 3,
+            correct_positions: vec![0],
+            contained_in_word: vec![1],
+            keyboard: keyboard.clone(),
+        };
+        board.apply(&checked, Some("Human"));
+        use LetterState::*;
+        assert_eq!(
+            board.rows,
+            vec![Row {
+                word: "human".to_string(),
+                letters: vec![Correct, Present, Absent, Absent, Absent]
+            }]
+        );
+        assert_eq!(board.keyboard, keyboard);
+
+        // an earlier game timing out does not end this one
+        board.apply(
+            &SessionEvent::GameOver {
+                game_id: 2,
+                result: SessionResult::Lose,
+            },
+            None,
+        );
+        assert!(board.is_playing());
+
+        board.apply(
+            &SessionEvent::GameOver {
+                game_id: 3,
+                result: SessionResult::Win,
+            },
+            Some("house"),
+        );
+        assert!(!board.is_playing());
+        assert_eq!(board.rows[1].letters, vec![Correct; WORD_LENGTH]);
+        assert_eq!(
+            board.render(false).lines().next(),
+            Some("[H] (U)  M-  A-  N-")
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Plays Wordle from the terminal through game-session.
+//!
+//! Offline play runs both programs in gtest, so build them first (`cargo build` in the
+//! workspace root), then `cargo run -p wordle-cli`.
+
+mod backend;
+mod board;
+
+use std::{
+    env,
+    io::{self, BufRead, Write},
+    process,
+};
+
+use game_session_io::{SessionAction, SessionEvent};
+
+use backend::{Backend, GtestBackend};
+use board::Board;
+
+const HELP: &str = "\
+commands:
+  start [bank]   start a game, with a word from `bank` if given
+  practice [bank]
+                 start a practice game, it does not count towards the stats
+  <word>         guess a word
+  forfeit        give up the current game
+  wait <blocks>  let blocks pass
+  board          draw the board again
+  help           show this help
+  quit           leave";
+
+const USAGE: &str = "usage: wordle-cli [--wasm-dir <dir>] [--player <id>] [--no-colour]";
+
+struct Args {
+    wasm_dir: String,
+    player: u64,
+    colour: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut parsed = Args {
+        wasm_dir: "target/wasm32-unknown-unknown/debug".to_string(),
+        player: 10,
+        colour: true,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--wasm-dir" => parsed.wasm_dir = value()?,
+            "--player" => {
+                parsed.player = value()?
+                    .parse()
+                    .map_err(|_| "--player takes a number".to_string())?
+            }
+            "--no-colour" => parsed.colour = false,
+            other => return Err(format!("unknown argument {other}")),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Updates the board with the events of one command and tells the player about them.
+fn show(board: &mut Board, events: Vec<SessionEvent>, guess: Option<&str>, colour: bool) {
+    let mut redraw = false;
+    for event in &events {
+        board.apply(event, guess);
+        match event {
+            SessionEvent::GameStarted { game_id, alphabet } => {
+                println!(
+                    "game {game_id} started, guess a {}-letter word ({alphabet:?})",
+                    wordle_io::WORD_LENGTH
+                );
+                redraw = true;
+            }
+            SessionEvent::WordChecked { .. } => redraw = true,
+            SessionEvent::GameOver { game_id, result } => {
+                redraw = true;
+                println!("game {game_id} over: {result:?}");
+            }
+            SessionEvent::Error(error) => println!("error: {error:?}"),
+            event => println!("{event:?}"),
+        }
+    }
+    if redraw {
+        print!("{}", board.render(colour));
+    }
+}
+
+fn run(backend: &mut dyn Backend, colour: bool) -> io::Result<()> {
+    let mut board = Board::default();
+    println!("{HELP}");
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+
+        let (events, guess) = match command {
+            "quit" | "exit" => return Ok(()),
+            "help" => {
+                println!("{HELP}");
+                continue;
+            }
+            "board" => {
+                print!("{}", board.render(colour));
+                continue;
+            }
+            "start" => (
+                backend.send(SessionAction::StartGame {
+                    bank: words.next().map(str::to_string),
+                }),
+                None,
+            ),
+            "practice" => {
+                let bank = words.next().map(str::to_string);
+                (
+                    backend.send(SessionAction::StartPractice {
+                        bank,
+                        unlimited_guesses: false,
+                    }),
+                    None,
+                )
+            }
+            "forfeit" => {
+                let Some(game_id) = board.game_id.filter(|_| board.is_playing()) else {
+                    println!("no game in progress");
+                    continue;
+                };
+                (backend.send(SessionAction::Forfeit { game_id }), None)
+            }
+            "wait" => {
+                let Some(blocks) = words.next().and_then(|blocks| blocks.parse().ok()) else {
+                    println!("wait takes a number of blocks");
+                    continue;
+                };
+                (backend.wait(blocks), None)
+            }
+            word => {
+                let Some(game_id) = board.game_id.filter(|_| board.is_playing()) else {
+                    println!("no game in progress, `start` one first");
+                    continue;
+                };
+                (
+                    backend.send(SessionAction::CheckWord {
+                        game_id,
+                        word: word.to_string(),
+                    }),
+                    Some(word),
+                )
+            }
+        };
+
+        match events {
+            Ok(events) => show(&mut board, events, guess, colour),
+            Err(error) => println!("error: {error}"),
+        }
+    }
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|error| {
+        eprintln!("{error}\n{USAGE}");
+        process::exit(2);
+    });
+    let mut backend = GtestBackend::new(&args.wasm_dir, args.player).unwrap_or_else(|error| {
+        eprintln!("{error}, build the programs with `cargo build` first");
+        process::exit(1);
+    });
+    if let Err(error) = run(&mut backend, args.colour) {
+        eprintln!("{error}");
+        process::exit(1);
+    }
+}
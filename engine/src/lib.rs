@@ -0,0 +1,254 @@
+//! Rules of the game, shared by the Wordle and game-session programs: scoring a guess
+//! against the answer, counting attempts and telling when a game is over.
+//!
+//! Words are compared letter by letter as `char`s, so callers pass them normalized
+//! (see `wordle_io::Alphabet::normalize`).
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+pub const WORD_LENGTH: usize = 5;
+pub const MAX_ATTEMPTS: u8 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineError {
+    /// The word is not `WORD_LENGTH` letters long.
+    WrongLength,
+    /// The game is already won or lost.
+    GameOver,
+}
+
+/// Result of checking a guess, as positions in the guess.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Feedback {
+    /// Letters in the same place as in the answer.
+    pub correct_positions: Vec<u8>,
+    /// Letters that are somewhere else in the answer.
+    pub contained_in_word: Vec<u8>,
+}
+
+impl Feedback {
+    pub fn is_win(&self) -> bool {
+        self.correct_positions.len() == WORD_LENGTH
+    }
+}
+
+/// Scores `guess` against `answer`. A letter that is not in its place counts as contained
+/// whenever the answer has it, however many times it occurs in either word.
+pub fn score(answer: &str, guess: &str) -> Result<Feedback, EngineError> {
+    check_length(answer)?;
+    check_length(guess)?;
+
+    let mut feedback = Feedback::default();
+    for (i, (a, g)) in answer.chars().zip(guess.chars()).enumerate() {
+        if a == g {
+            feedback.correct_positions.push(i as u8);
+        } else if answer.contains(g) {
+            feedback.contained_in_word.push(i as u8);
+        }
+    }
+    Ok(feedback)
+}
+
+pub fn check_length(word: &str) -> Result<(), EngineError> {
+    if word.chars().count() == WORD_LENGTH {
+        Ok(())
+    } else {
+        Err(EngineError::WrongLength)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Ongoing,
+    Win,
+    Lose,
+}
+
+/// Attempts and outcome of a game, as followed by someone who only sees the feedback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    attempts: u8,
+    outcome: Outcome,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Self {
+            attempts: 0,
+            outcome: Outcome::Ongoing,
+        }
+    }
+
+    /// Progress of a game that has already used `attempts` guesses without winning.
+    pub fn resume(attempts: u8) -> Self {
+        let outcome = if attempts >= MAX_ATTEMPTS {
+            Outcome::Lose
+        } else {
+            Outcome::Ongoing
+        };
+        Self { attempts, outcome }
+    }
+
+    pub fn attempts(&self) -> u8 {
+        self.attempts
+    }
+
+    pub fn remaining(&self) -> u8 {
+        MAX_ATTEMPTS.saturating_sub(self.attempts)
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    /// Counts a guess with its feedback: it wins the game, or loses it if it was the last attempt.
+    pub fn apply(&mut self, feedback: &Feedback) -> Result<Outcome, EngineError> {
+        if self.outcome != Outcome::Ongoing {
+            return Err(EngineError::GameOver);
+        }
+        self.attempts += 1;
+        self.outcome = if feedback.is_win() {
+            Outcome::Win
+        } else if self.attempts >= MAX_ATTEMPTS {
+            Outcome::Lose
+        } else {
+            Outcome::Ongoing
+        };
+        Ok(self.outcome)
+    }
+}
+
+/// A game that knows its answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Game<'a> {
+    answer: &'a str,
+    progress: Progress,
+}
+
+impl<'a> Game<'a> {
+    pub fn new(answer: &'a str) -> Result<Self, EngineError> {
+        check_length(answer)?;
+        Ok(Self {
+            answer,
+            progress: Progress::new(),
+        })
+    }
+
+    pub fn progress(&self) -> Progress {
+        self.progress
+    }
+
+    pub fn guess(&mut self, guess: &str) -> Result<Feedback, EngineError> {
+        if self.progress.outcome() != Outcome::Ongoing {
+            return Err(EngineError::GameOver);
+        }
+        let feedback = score(self.answer, guess)?;
+        self.progress.apply(&feedback)?;
+        Ok(feedback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn feedback(correct_positions: Vec<u8>, contained_in_word: Vec<u8>) -> Feedback {
+        Feedback {
+            correct_positions,
+            contained_in_word,
+        }
+    }
+
+    #[test]
+    fn scores_guesses() {
+        assert_eq!(
+            score("house", "house"),
+            Ok(feedback(vec![0, 1, 2, 3, 4], vec![]))
+        );
+        assert_eq!(score("house", "human"), Ok(feedback(vec![0], vec![1])));
+        assert_eq!(
+            score("house", "horse"),
+            Ok(feedback(vec![0, 1, 3, 4], vec![]))
+        );
+        assert_eq!(score("house", "sound"), Ok(feedback(vec![1, 2], vec![0])));
+        assert_eq!(
+            score("книга", "казка"),
+            Ok(feedback(vec![0, 4], vec![1, 3]))
+        );
+        assert_eq!(score("größe", "bäume"), Ok(feedback(vec![4], vec![])));
+    }
+
+    #[test]
+    fn repeated_letters_count_as_contained() {
+        // both 'o's of "moose" are reported although "house" has one
+        assert_eq!(
+            score("house", "moose"),
+            Ok(feedback(vec![1, 3, 4], vec![2]))
+        );
+    }
+
+    #[test]
+    fn rejects_words_of_the_wrong_length() {
+        assert_eq!(score("house", "hous"), Err(EngineError::WrongLength));
+        assert_eq!(score("house", "houses"), Err(EngineError::WrongLength));
+        assert_eq!(Game::new("hi").err(), Some(EngineError::WrongLength));
+        assert_eq!(check_length("місто"), Ok(()));
+    }
+
+    #[test]
+    fn game_is_won_by_the_answer() {
+        let mut game = Game::new("house").unwrap();
+        assert!(!game.guess("human").unwrap().is_win());
+        assert!(game.guess("house").unwrap().is_win());
+        assert_eq!(game.progress().outcome(), Outcome::Win);
+        assert_eq!(game.progress().attempts(), 2);
+        assert_eq!(game.guess("house"), Err(EngineError::GameOver));
+    }
+
+    #[test]
+    fn game_is_lost_after_the_last_attempt() {
+        let mut game = Game::new("house").unwrap();
+        for attempt in 1..MAX_ATTEMPTS {
+            game.guess("human").unwrap();
+            assert_eq!(game.progress().outcome(), Outcome::Ongoing);
+            assert_eq!(game.progress().remaining(), MAX_ATTEMPTS - attempt);
+        }
+        game.guess("human").unwrap();
+        assert_eq!(game.progress().outcome(), Outcome::Lose);
+        assert_eq!(game.guess("house"), Err(EngineError::GameOver));
+    }
+
+    #[test]
+    fn winning_on_the_last_attempt_is_a_win() {
+        let mut progress = Progress::resume(MAX_ATTEMPTS - 1);
+        let win = score("house", "house").unwrap();
+        assert_eq!(progress.apply(&win), Ok(Outcome::Win));
+    }
+
+    #[test]
+    fn invalid_guesses_do_not_use_attempts() {
+        let mut game = Game::new("house").unwrap();
+        assert_eq!(game.guess("hou"), Err(EngineError::WrongLength));
+        assert_eq!(game.progress().attempts(), 0);
+    }
+
+    #[test]
+    fn resumes_progress() {
+        assert_eq!(Progress::resume(0), Progress::new());
+        assert_eq!(Progress::resume(MAX_ATTEMPTS).outcome(), Outcome::Lose);
+        assert_eq!(
+            Progress::resume(MAX_ATTEMPTS).apply(&Feedback::default()),
+            Err(EngineError::GameOver)
+        );
+    }
+}
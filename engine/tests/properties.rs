@@ -0,0 +1,68 @@
+use proptest::prelude::*;
+use wordle_engine::*;
+
+fn word() -> impl Strategy<Value = String> {
+    // a small alphabet makes shared and repeated letters likely
+    proptest::collection::vec(
+        prop::sample::select(vec!['a', 'e', 'h', 'o', 's', 'u', 'ё', 'ß']),
+        WORD_LENGTH,
+    )
+    .prop_map(|letters| letters.into_iter().collect())
+}
+
+proptest! {
+    #[test]
+    fn feedback_matches_the_letters(answer in word(), guess in word()) {
+        let feedback = score(&answer, &guess).unwrap();
+        let answer_letters: Vec<char> = answer.chars().collect();
+        for (i, letter) in guess.chars().enumerate() {
+            let i = i as u8;
+            let correct = feedback.correct_positions.contains(&i);
+            let contained = feedback.contained_in_word.contains(&i);
+            prop_assert_eq!(correct, answer_letters[i as usize] == letter);
+            prop_assert_eq!(contained, !correct && answer_letters.contains(&letter));
+        }
+    }
+
+    #[test]
+    fn feedback_positions_are_sorted_and_in_range(answer in word(), guess in word()) {
+        let feedback = score(&answer, &guess).unwrap();
+        for positions in [&feedback.correct_positions, &feedback.contained_in_word] {
+            prop_assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+            prop_assert!(positions.iter().all(|i| (*i as usize) < WORD_LENGTH));
+        }
+    }
+
+    #[test]
+    fn only_the_answer_wins(answer in word(), guess in word()) {
+        prop_assert_eq!(score(&answer, &guess).unwrap().is_win(), answer == guess);
+    }
+
+    #[test]
+    fn games_end_within_the_attempt_limit(answer in word(), guesses in proptest::collection::vec(word(), 0..10)) {
+        let mut game = Game::new(&answer).unwrap();
+        for guess in &guesses {
+            let before = game.progress();
+            match game.guess(guess) {
+                Ok(feedback) => {
+                    prop_assert_eq!(before.outcome(), Outcome::Ongoing);
+                    prop_assert_eq!(game.progress().attempts(), before.attempts() + 1);
+                    let expected = if feedback.is_win() {
+                        Outcome::Win
+                    } else if game.progress().attempts() == MAX_ATTEMPTS {
+                        Outcome::Lose
+                    } else {
+                        Outcome::Ongoing
+                    };
+                    prop_assert_eq!(game.progress().outcome(), expected);
+                }
+                Err(error) => {
+                    prop_assert_eq!(error, EngineError::GameOver);
+                    prop_assert_ne!(before.outcome(), Outcome::Ongoing);
+                    prop_assert_eq!(game.progress(), before);
+                }
+            }
+        }
+        prop_assert!(game.progress().attempts() <= MAX_ATTEMPTS);
+    }
+}
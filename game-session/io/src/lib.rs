@@ -1,38 +1,370 @@
 #![no_std]
 
 use gmeta::{InOut, Out, Metadata};
-use gstd::{prelude::*, ActorId, MessageId, collections::HashMap};
-use wordle_io::Event;
+use blake2::{digest::consts::U32, Blake2b, Digest};
+use gstd::{prelude::*, ActorId, MessageId, ReservationId, collections::{BTreeMap, BTreeSet}};
+use wordle_io::{Alphabet, Event, GameId, WordleError, WordleInfo};
+
+mod migration;
+pub use migration::*;
+
+mod store;
+pub use store::*;
+
+mod scoring;
+pub use scoring::*;
+
+mod lifecycle;
+pub use lifecycle::*;
+
+/// SCALE codec of the messages, for clients that don't depend on gstd.
+pub use gstd::codec;
 
 pub struct GameSessionMetadata;
 
 impl Metadata for GameSessionMetadata {
-    type Init = InOut<ActorId, SessionEvent>;
+    type Init = InOut<GameSessionInit, SessionEvent>;
     type Handle = InOut<SessionAction, SessionEvent>;
-    type Others = ();
+    /// Lifecycle events sent to `SessionConfig::indexer`.
+    type Others = Out<SessionLogEntry>;
     type Reply = InOut<Event, SessionEvent>;
     type Signal = ();
-    type State = Out<State>;
+    /// An empty payload reads the whole `State`, which only suits small programs.
+    type State = InOut<StateQuery, StateReply>;
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct GameSessionInit {
+    /// First backend, asked for its `WordleInfo` during init, which fails if it does not
+    /// speak `wordle_io::PROTOCOL_VERSION` or has no `wordle_io::DEFAULT_BANK`.
+    pub wordle_program: ActorId,
+    pub config: SessionConfig,
+    /// First chunk of state exported from a previous program. When set, the program starts
+    /// in import mode and only accepts `ImportState`/`FinishImport` from the owner.
+    pub import: Option<StateChunk>,
+}
+
+#[derive(Debug, Clone, Copy, Encode, Decode, PartialEq, TypeInfo)]
+pub struct SessionConfig {
+    /// Number of blocks a finished session is kept before it is compacted into `UserStats`.
+    pub session_ttl: u32,
+    /// Number of blocks between two `SessionAction::Cleanup` runs.
+    pub cleanup_interval: u32,
+    /// Maximum number of sessions compacted by one `SessionAction::Cleanup` run.
+    pub cleanup_batch: u32,
+    /// Receiver of the `SessionLogEntry` stream, the zero address if nobody indexes it.
+    pub indexer: ActorId,
+    pub anti_abuse: AntiAbuseConfig,
+    pub hint_cost: HintCost,
+    pub scoring: ScoringConfig,
+}
+
+impl SessionConfig {
+    /// Checks that cleanup runs now and then: a zero interval would send `Cleanup` every
+    /// block, and a zero ttl or batch would compact sessions at once or never.
+    pub fn validate(&self) -> Result<(), SessionError> {
+        if self.session_ttl == 0 || self.cleanup_interval == 0 || self.cleanup_batch == 0 {
+            return Err(SessionError::InvalidConfig);
+        }
+        Ok(())
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            session_ttl: 14_400,
+            cleanup_interval: 1_200,
+            cleanup_batch: 50,
+            indexer: ActorId::zero(),
+            anti_abuse: AntiAbuseConfig::default(),
+            hint_cost: HintCost::default(),
+            scoring: ScoringConfig::default(),
+        }
+    }
+}
+
+/// Number of blocks counted as a day by `AntiAbuseConfig::max_games_per_day`.
+pub const BLOCKS_PER_DAY: u32 = 28_800;
+
+/// Rules against scripted play. A zero value turns the rule off, and they are all off by default.
+#[derive(Debug, Default, Clone, Copy, Encode, Decode, PartialEq, TypeInfo)]
+pub struct AntiAbuseConfig {
+    /// Blocks that have to pass after the game starts or after a guess before the next guess.
+    pub min_guess_interval: u32,
+    /// Games a user can start per `BLOCKS_PER_DAY` blocks.
+    pub max_games_per_day: u32,
+    /// Games won in fewer blocks than this count as suspiciously fast.
+    pub min_solve_blocks: u32,
+    /// Suspiciously fast wins a user is allowed before being flagged.
+    pub fast_solve_limit: u32,
+}
+
+/// What a `SessionAction::RequestHint` costs the player.
+#[derive(Debug, Default, Clone, Copy, Encode, Decode, PartialEq, Eq, TypeInfo)]
+pub enum HintCost {
+    /// The hint uses up an attempt, so it can't be taken with a single attempt left.
+    #[default]
+    Attempt,
+    /// The hint has to be sent with at least this value, the rest is refunded.
+    Fee(u128),
+    /// Points taken off the score of the game for each hint.
+    ScorePenalty(u32),
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum SessionAction {
-    StartGame,
-    CheckWord { word: String },
-    CheckGameStatus { user: ActorId },
+    /// Starts a game with a word from `bank`, or from `wordle_io::DEFAULT_BANK` if not set.
+    /// A player can have up to `MAX_OPEN_GAMES` games in progress at once.
+    StartGame { bank: Option<String> },
+    /// Starts a `GameMode::Practice` game. Practice games are not limited by `MAX_OPEN_GAMES`
+    /// or the daily game cap, and starting one ends the caller's practice game in progress.
+    StartPractice { bank: Option<String>, unlimited_guesses: bool },
+    CheckWord { game_id: GameId, word: String },
+    /// Gives up the game in progress, it ends as `SessionResult::Forfeited`.
+    Forfeit { game_id: GameId },
+    /// Reveals a letter of the answer at a position no guess or earlier hint has found,
+    /// at the `SessionConfig::hint_cost`.
+    RequestHint { game_id: GameId },
+    CheckGameStatus { user: ActorId, game_id: GameId },
+    Cleanup,
+    /// Exports the sessions in insertion order, then the stats of every user. A page holds
+    /// `limit` of them from `cursor` on, clamped to `1..=MAX_EXPORT_PAGE`.
+    ExportState { cursor: u32, limit: u32 },
+    ImportState { chunk: StateChunk },
+    FinishImport,
+    TransferOwnership { new_owner: ActorId },
+    Pause,
+    Unpause,
+    /// Pauses the program and ends every ongoing game as `SessionResult::Void`, at most
+    /// `EMERGENCY_STOP_BATCH` of them at once. The program sends itself the rest.
+    EmergencyStop,
+    SetIndexer { indexer: ActorId },
+    SetAntiAbuse { config: AntiAbuseConfig },
+    SetHintCost { cost: HintCost },
+    /// Replaces the points formula. Games finished from then on are scored under a new
+    /// scoring version, and the leaderboards only count points of the current one.
+    SetScoring { scoring: ScoringConfig },
+    /// Flags or clears a user, flagged users are left out of the leaderboards. Moderators only.
+    SetFlagged { user: ActorId, flagged: bool },
+    /// Registers another Wordle program once it passed the `GetInfo` handshake. New games are
+    /// spread over the healthy backends.
+    AddBackend { program: ActorId },
+    /// Stops starting games on a backend, games already on it still go there.
+    RemoveBackend { program: ActorId },
+    AddModerator { moderator: ActorId },
+    RemoveModerator { moderator: ActorId },
+    /// Submits a word pack for review. Once approved it can be played as a bank named `name`.
+    SubmitWordPack { name: String, alphabet: Alphabet, words: Vec<String> },
+    ReviewWordPack { name: String, approve: bool },
+    /// Challenges `friend` to guess `word`, which has to be in the dictionary of `bank`
+    /// (`wordle_io::DEFAULT_BANK` if not set). The word is sent in plain text, so anyone
+    /// reading the message can see it, it is only kept out of `State` and the replies.
+    ChallengeFriend { friend: ActorId, word: String, bank: Option<String> },
+    /// Starts a game on the word of a pending challenge sent to the caller.
+    AcceptChallenge { challenge_id: ChallengeId },
 }
 
 #[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
 pub enum SessionEvent {
     Initialized,
-    GameStarted,
+    GameStarted {
+        game_id: GameId,
+        alphabet: Alphabet,
+    },
     WordChecked {
+        game_id: GameId,
         correct_positions: Vec<u8>,
         contained_in_word: Vec<u8>,
+        /// State of every letter of the game's alphabet after this guess, in `Alphabet::letters` order.
+        keyboard: Vec<LetterState>,
+    },
+    HintRevealed {
+        game_id: GameId,
+        position: u8,
+        letter: String,
     },
     GameOver {
-        result: SessionResult
+        game_id: GameId,
+        result: SessionResult,
+    },
+    StateExported {
+        chunk: StateChunk,
+        next_cursor: Option<u32>,
+    },
+    StateImported {
+        version: u32,
+    },
+    ImportFinished,
+    OwnershipTransferred {
+        new_owner: ActorId,
+    },
+    Paused,
+    Unpaused,
+    EmergencyStopped {
+        /// Games voided by the first batch.
+        voided: u32,
+    },
+    IndexerSet {
+        indexer: ActorId,
+    },
+    AntiAbuseSet {
+        config: AntiAbuseConfig,
+    },
+    HintCostSet {
+        cost: HintCost,
+    },
+    ScoringSet {
+        version: u32,
+        scoring: ScoringConfig,
+    },
+    FlaggedSet {
+        user: ActorId,
+        flagged: bool,
+    },
+    BackendAdded {
+        program: ActorId,
+    },
+    BackendRemoved {
+        program: ActorId,
+    },
+    ModeratorAdded {
+        moderator: ActorId,
     },
+    ModeratorRemoved {
+        moderator: ActorId,
+    },
+    WordPackSubmitted {
+        name: String,
+    },
+    /// For an approval the pack becomes playable once the Wordle program has registered it.
+    WordPackReviewed {
+        name: String,
+        approve: bool,
+    },
+    ChallengeCreated {
+        challenge_id: ChallengeId,
+        word_hash: [u8; 32],
+    },
+    /// Sent to the challenged friend.
+    ChallengeReceived {
+        challenge_id: ChallengeId,
+        challenger: ActorId,
+        word_hash: [u8; 32],
+    },
+    /// Sent to both players once the challenged friend's game is over.
+    ChallengeFinished {
+        challenge_id: ChallengeId,
+        result: SessionResult,
+    },
+    Error(SessionError),
+}
+
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+pub enum SessionError {
+    /// The Wordle program rejected the request.
+    Wordle(WordleError),
+    /// The action can only be sent by the program owner.
+    NotOwner,
+    /// The program is still importing state and does not accept games yet.
+    ImportInProgress,
+    /// State can only be imported right after an init with `import` set.
+    NotImporting,
+    /// New games can't be started while the program is paused.
+    Paused,
+    /// Not enough gas was attached to reserve gas for the game timeout. An imported chunk
+    /// needs a reservation for each of its open games, a smaller page may fit.
+    NotEnoughGasForTimeout,
+    /// The action can only be sent by a moderator or the owner.
+    NotModerator,
+    WordPackExists,
+    UnknownWordPack,
+    /// Only pending word packs can be reviewed.
+    WordPackAlreadyReviewed,
+    /// The pack is empty, too big, or has a word that is not valid in its alphabet.
+    InvalidWordPack,
+    /// Players can't challenge themselves.
+    InvalidChallenge,
+    /// There is no pending challenge with this id for the caller.
+    UnknownChallenge,
+    /// The caller has no game in progress with this id.
+    NoGameInProgress,
+    /// The game is waiting for the Wordle program, or can't take the action in its status.
+    GameBusy,
+    /// The caller already has `MAX_OPEN_GAMES` games in progress.
+    TooManyOpenGames,
+    /// `AntiAbuseConfig::min_guess_interval` blocks have not passed since the last guess.
+    GuessTooSoon,
+    /// The caller already started `AntiAbuseConfig::max_games_per_day` games today.
+    DailyGameLimit,
+    /// The Wordle program does not support the `wordle_io::Feature` the action needs.
+    NotSupported,
+    /// A hint costing an attempt would use up the last one.
+    NoAttemptsLeft,
+    /// The value sent with `RequestHint` is below `HintCost::Fee`.
+    HintFeeTooLow,
+    /// No healthy backend answered in time.
+    BackendUnavailable,
+    /// The program did not pass the `GetInfo` handshake.
+    IncompatibleBackend,
+    /// The program is already a backend, or an `AddBackend` for it waits for the handshake.
+    BackendExists,
+    UnknownBackend,
+    /// `SessionConfig::session_ttl`, `cleanup_interval` and `cleanup_batch` can't be zero.
+    InvalidConfig,
+}
+
+impl SessionError {
+    /// Whether the error says more about the backend than about the request, so the
+    /// request may succeed on another backend.
+    pub fn is_backend_failure(&self) -> bool {
+        matches!(
+            self,
+            SessionError::BackendUnavailable
+                | SessionError::Wordle(WordleError::Paused)
+                | SessionError::Wordle(WordleError::UnauthorizedSessionProgram)
+        )
+    }
+}
+
+/// What is known about a letter, ordered from the least to the most informative.
+#[derive(Debug, Clone, Copy, Encode, Decode, PartialEq, Eq, PartialOrd, Ord, TypeInfo)]
+pub enum LetterState {
+    Unknown,
+    Absent,
+    Present,
+    Correct,
+}
+
+pub fn empty_keyboard(alphabet: Alphabet) -> Vec<LetterState> {
+    vec![LetterState::Unknown; alphabet.letters().len()]
+}
+
+/// Folds the feedback for `word` into `keyboard`. A letter state only ever improves,
+/// and a letter known to be absent stays absent.
+pub fn update_keyboard(
+    keyboard: &mut [LetterState],
+    alphabet: Alphabet,
+    word: &str,
+    correct_positions: &[u8],
+    contained_in_word: &[u8],
+) {
+    for (i, letter) in word.chars().enumerate() {
+        let Some(index) = alphabet.index_of(letter) else {
+            continue;
+        };
+        let key = &mut keyboard[index];
+        let state = if correct_positions.contains(&(i as u8)) {
+            LetterState::Correct
+        } else if contained_in_word.contains(&(i as u8)) {
+            LetterState::Present
+        } else {
+            LetterState::Absent
+        };
+        if *key != LetterState::Absent && state > *key {
+            *key = state;
+        }
+    }
 }
 
 #[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
@@ -40,15 +372,35 @@ pub enum SessionResult {
     Ongoing,
     Win,
     Lose,
+    /// The game was ended by the owner and does not count towards stats.
+    Void,
+    /// The game ran out of time, whether noticed on a guess or by `CheckGameStatus`.
+    TimedOut,
+    /// The player gave up with `SessionAction::Forfeit`, or restarted a practice game.
+    Forfeited,
 }
 
-#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
-pub enum SessionStatus {
-    StartGameWaiting,
-    StartGameSent,
-    CheckWordWaiting,
-    CheckWordSent,
-    ReplyReceived(SessionEvent),
+
+/// Whether a game counts. Practice games are left out of the stats, the leaderboards and
+/// the word pack play counts.
+#[derive(Debug, Default, Clone, Copy, Encode, Decode, PartialEq, Eq, TypeInfo)]
+pub enum GameMode {
+    #[default]
+    Ranked,
+    Practice {
+        /// The game only ends on a win or a timeout, not after `MAX_ATTEMPTS` guesses.
+        unlimited_guesses: bool,
+    },
+}
+
+impl GameMode {
+    pub fn is_practice(&self) -> bool {
+        matches!(self, GameMode::Practice { .. })
+    }
+
+    pub fn unlimited_guesses(&self) -> bool {
+        matches!(self, GameMode::Practice { unlimited_guesses: true })
+    }
 }
 
 type SentMessageId = MessageId;
@@ -58,48 +410,618 @@ type OriginalMessageId = MessageId;
 #[codec(crate = gstd::codec)]
 #[scale_info(crate = gstd::scale_info)]
 pub struct Session {
+    pub game_id: GameId,
+    pub mode: GameMode,
+    /// Wordle program the game was started on, every guess goes there.
+    pub backend: ActorId,
+    /// Backends asked to start the game, including the one it ended up on.
+    pub start_attempts: u8,
     pub start_block: u32,
+    pub last_active_block: u32,
     pub check_count: u8,
     pub msg_ids: (SentMessageId, OriginalMessageId),
     pub status: SessionStatus,
     pub result: SessionResult,
+    pub bank: String,
+    pub alphabet: Alphabet,
+    pub keyboard: Vec<LetterState>,
+    /// Challenge whose word is being played, if any.
+    pub challenge: Option<ChallengeId>,
+    /// Positions of the answer found by a guess or a hint, hints only reveal the others.
+    pub known_positions: Vec<u8>,
+    /// Letters revealed by hints, with their positions.
+    pub hints: Vec<(u8, String)>,
+    /// The normalized answer, known once the game is won.
+    pub answer: Option<String>,
+    /// Set when a ranked game ends, challenge games are not scored.
+    pub score: Option<Score>,
+    /// Gas reserved when the game is requested and held until it ends. A timeout pays for
+    /// its `GameOver` from it, any other ending gives it back.
+    pub timeout_reservation: Option<ReservationId>,
+}
+
+/// Games a player can have in progress at once.
+pub const MAX_OPEN_GAMES: usize = 5;
+
+/// Games voided by one `SessionAction::EmergencyStop` run.
+pub const EMERGENCY_STOP_BATCH: usize = 20;
+
+impl Session {
+    /// Whether the game has been started and is not over yet.
+    pub fn is_open(&self) -> bool {
+        self.result == SessionResult::Ongoing && self.status != SessionStatus::StartGameWaiting
+    }
+}
+
+#[derive(Debug, Default, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct UserStats {
+    pub games_played: u32,
+    pub wins: u32,
+    /// Every game that was not won, timeouts and forfeits included.
+    pub losses: u32,
+    pub timeouts: u32,
+    pub forfeits: u32,
+    pub last_active_block: u32,
+    /// Games played by others in the word packs this user created.
+    pub pack_plays: u32,
+    /// Results split by the word bank the games were played in.
+    pub banks: BTreeMap<String, BankStats>,
+    /// Day, in `BLOCKS_PER_DAY` blocks, `games_today` counts the games of.
+    pub day: u32,
+    pub games_today: u32,
+    pub hints_used: u32,
+    pub points: Points,
+    /// Wins faster than `AntiAbuseConfig::min_solve_blocks`.
+    pub fast_solves: u32,
+    /// Set when the user has too many fast solves or by a moderator, flagged users
+    /// are left out of the leaderboards.
+    pub flagged: bool,
+}
+
+#[derive(Debug, Default, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct BankStats {
+    pub games_played: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub points: Points,
+}
+
+impl UserStats {
+    /// Counts a finished game. `bank` is `None` for a game left out of the leaderboards, it
+    /// only counts towards the totals.
+    pub fn record(&mut self, bank: Option<&str>, result: &SessionResult, block: u32) {
+        if matches!(result, SessionResult::Ongoing | SessionResult::Void) {
+            return;
+        }
+        let won = *result == SessionResult::Win;
+        if let Some(bank) = bank {
+            let bank_stats = self.banks.entry(bank.to_string()).or_default();
+            bank_stats.games_played += 1;
+            if won {
+                bank_stats.wins += 1;
+            } else {
+                bank_stats.losses += 1;
+            }
+        }
+        if won {
+            self.wins += 1;
+        } else {
+            self.losses += 1;
+        }
+        match result {
+            SessionResult::TimedOut => self.timeouts += 1,
+            SessionResult::Forfeited => self.forfeits += 1,
+            _ => {},
+        }
+        self.games_played += 1;
+        self.last_active_block = block;
+    }
+
+    pub fn record_score(&mut self, bank: &str, score: &Score) {
+        self.points.add(score);
+        if let Some(bank_stats) = self.banks.get_mut(bank) {
+            bank_stats.points.add(score);
+        }
+    }
+
+    /// Games started on the day of `block`.
+    pub fn games_on_day_of(&self, block: u32) -> u32 {
+        if self.day == block / BLOCKS_PER_DAY {
+            self.games_today
+        } else {
+            0
+        }
+    }
+
+    pub fn count_game(&mut self, block: u32) {
+        self.games_today = self.games_on_day_of(block) + 1;
+        self.day = block / BLOCKS_PER_DAY;
+    }
+
+    /// Counts a win that took `solve_blocks` blocks, flagging the user once there
+    /// are more fast solves than `config` allows.
+    pub fn record_solve(&mut self, solve_blocks: u32, config: &AntiAbuseConfig) {
+        if solve_blocks < config.min_solve_blocks {
+            self.fast_solves += 1;
+            if self.fast_solves > config.fast_solve_limit {
+                self.flagged = true;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Encode, Decode, PartialEq, Eq, TypeInfo)]
+pub enum WordPackStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+pub const MAX_WORD_PACK_SIZE: usize = 200;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordPack {
+    pub creator: ActorId,
+    pub alphabet: Alphabet,
+    /// Normalized words, they are sent to the Wordle program on approval.
+    pub words: Vec<String>,
+    pub status: WordPackStatus,
+    pub play_count: u32,
+    /// `AddBank` message sent to the Wordle program for an approval still waiting for its reply.
+    pub registration: Option<MessageId>,
 }
 
+/// Word pack as exposed in `State`, without its words.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct WordPackInfo {
+    pub name: String,
+    pub creator: ActorId,
+    pub alphabet: Alphabet,
+    pub size: u32,
+    pub status: WordPackStatus,
+    pub play_count: u32,
+}
+
+pub type ChallengeId = u64;
+
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+pub enum ChallengeStatus {
+    /// Waiting for the Wordle program to check the word against the dictionary.
+    Validating,
+    Invalid(WordleError),
+    /// Waiting for the friend to accept it.
+    Pending,
+    Accepted,
+    Finished(SessionResult),
+}
+
+/// Hash committing to a challenge word, so the players can check once the game is over that
+/// it was played on the word the challenger picked. It does not keep the word secret, which
+/// `SessionAction::ChallengeFriend` carries in plain text. The salt is revealed with the word.
+pub fn challenge_commitment(salt: &[u8; 32], word: &str) -> [u8; 32] {
+    Blake2b::<U32>::new()
+        .chain_update(salt)
+        .chain_update(word.as_bytes())
+        .finalize()
+        .into()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Challenge {
+    pub challenger: ActorId,
+    pub friend: ActorId,
+    pub bank: String,
+    /// Normalized word, left out of `State` until the game is over.
+    pub word: String,
+    pub salt: [u8; 32],
+    pub word_hash: [u8; 32],
+    pub status: ChallengeStatus,
+    /// `ValidateWord` sent to the Wordle program and the `ChallengeFriend` waiting for its reply.
+    pub msg_ids: (SentMessageId, OriginalMessageId),
+}
+
+/// Challenge as exposed in `State`: the word and salt are only revealed once it is finished.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct ChallengeInfo {
+    pub challenge_id: ChallengeId,
+    pub challenger: ActorId,
+    pub friend: ActorId,
+    pub bank: String,
+    pub word_hash: [u8; 32],
+    pub status: ChallengeStatus,
+    pub revealed: Option<(String, [u8; 32])>,
+}
+
+impl From<(&ChallengeId, &Challenge)> for ChallengeInfo {
+    fn from((challenge_id, challenge): (&ChallengeId, &Challenge)) -> Self {
+        let revealed = matches!(challenge.status, ChallengeStatus::Finished(_))
+            .then(|| (challenge.word.clone(), challenge.salt));
+        Self {
+            challenge_id: *challenge_id,
+            challenger: challenge.challenger,
+            friend: challenge.friend,
+            bank: challenge.bank.clone(),
+            word_hash: challenge.word_hash,
+            status: challenge.status.clone(),
+            revealed,
+        }
+    }
+}
+
+/// Lifecycle transition of a session, as reported to the indexer.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+pub enum SessionLogEvent {
+    GameStarted {
+        bank: String,
+        challenge: Option<ChallengeId>,
+        mode: GameMode,
+    },
+    Guess {
+        word: String,
+        correct_positions: Vec<u8>,
+        contained_in_word: Vec<u8>,
+    },
+    Hint {
+        position: u8,
+        letter: String,
+    },
+    Win,
+    Lose,
+    /// The game ran out of time, whether noticed on a guess or by `CheckGameStatus`.
+    Timeout,
+    Forfeit,
+    /// Ended by `EmergencyStop`.
+    Void,
+}
+
+/// Entry of the event log sent to `SessionConfig::indexer`. Sequence numbers start at zero
+/// and increase by one with every entry of a program, so gaps tell the indexer it missed some.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct SessionLogEntry {
+    pub seq: u64,
+    pub block: u32,
+    pub user: ActorId,
+    pub game_id: GameId,
+    pub event: SessionLogEvent,
+}
+
+pub const LEADERBOARD_SIZE: usize = 10;
+
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct LeaderboardEntry {
+    pub user: ActorId,
+    /// Points under the current scoring version.
+    pub points: u64,
+    pub wins: u32,
+    pub games_played: u32,
+}
+
+#[derive(Debug, Default, Clone, Copy, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct GasMetrics {
+    /// Gas held in the timeout reservations of games that have not ended yet.
+    pub reserved_gas: u64,
+    pub active_reservations: u32,
+    /// Gas of the reservations spent on telling players their game timed out.
+    pub spent_gas: u64,
+    /// Program balance as of the last message it handled, what is left to fund new games.
+    pub balance: u128,
+}
+
+/// Blocks a backend has to reply in before the request counts as failed.
+pub const BACKEND_REPLY_TIMEOUT: u32 = 20;
+/// Failures in a row after which a backend is left out of new games.
+pub const BACKEND_FAILURE_LIMIT: u32 = 3;
+/// Blocks after its latest failure an unhealthy backend is given another try.
+pub const BACKEND_RETRY_BLOCKS: u32 = 600;
+
+/// A Wordle program games can be started on.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct Backend {
+    pub program: ActorId,
+    /// What the program answered to `GetInfo`, with the banks added since.
+    pub info: WordleInfo,
+    /// Failures since the last successful reply: reply timeouts, error replies and
+    /// errors for which `SessionError::is_backend_failure` holds.
+    pub failures: u32,
+    pub last_failure_block: u32,
+}
+
+impl Backend {
+    pub fn new(program: ActorId, info: WordleInfo) -> Self {
+        Self { program, info, failures: 0, last_failure_block: 0 }
+    }
+
+    pub fn is_healthy(&self, block: u32) -> bool {
+        self.failures < BACKEND_FAILURE_LIMIT || block >= self.last_failure_block.saturating_add(BACKEND_RETRY_BLOCKS)
+    }
+
+    pub fn record_success(&mut self) {
+        self.failures = 0;
+    }
+
+    pub fn record_failure(&mut self, block: u32) {
+        self.failures += 1;
+        self.last_failure_block = block;
+    }
+}
+
+/// Largest page of sessions replied to `StateQuery::Sessions`.
+pub const MAX_SESSIONS_PAGE: u32 = 100;
+
+/// Largest page of sessions and stats exported by `SessionAction::ExportState`.
+pub const MAX_EXPORT_PAGE: u32 = 100;
+
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum StateQuery {
+    /// The first registered backend.
     WordleProgram,
-    UserSession { user: ActorId },
+    Backends,
+    UserSession { user: ActorId, game_id: GameId },
+    /// Games of `user` that are still in progress, ordered by game id.
+    OpenGames { user: ActorId },
+    /// Sessions matching `filter` ordered by user and game id, starting after `cursor`.
+    /// At most `limit` of them are replied, capped at `MAX_SESSIONS_PAGE`.
+    Sessions { filter: SessionFilter, cursor: Option<SessionKey>, limit: u32 },
+}
+
+#[derive(Debug, Encode, Decode, TypeInfo)]
+pub enum StateReply {
+    WordleProgram(ActorId),
+    Backends(Vec<Backend>),
+    UserSession(Option<Session>),
+    OpenGames(Vec<Session>),
+    /// `next_cursor` is set if there may be more matching sessions.
+    Sessions {
+        sessions: Vec<(ActorId, Session)>,
+        next_cursor: Option<SessionKey>,
+    },
+}
+
+/// Conditions a session has to meet, unset ones match everything.
+#[derive(Debug, Default, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct SessionFilter {
+    /// Only the variant is compared, so any `CheckWordReplied` status matches `CheckWordReplied`.
+    pub status: Option<SessionStatus>,
+    pub result: Option<SessionResult>,
+    /// First block of the start block range, inclusive.
+    pub start_block_from: Option<u32>,
+    /// Last block of the start block range, inclusive.
+    pub start_block_to: Option<u32>,
+}
+
+impl SessionFilter {
+    pub fn matches(&self, session: &Session) -> bool {
+        self.status.as_ref().map_or(true, |status| core::mem::discriminant(status) == core::mem::discriminant(&session.status))
+            && self.result.as_ref().map_or(true, |result| *result == session.result)
+            && self.start_block_from.map_or(true, |from| session.start_block >= from)
+            && self.start_block_to.map_or(true, |to| session.start_block <= to)
+    }
 }
 
 #[derive(Default, Debug, PartialEq, Clone)]
 pub struct GameSessionState {
-    pub wordle_program: ActorId,
-    pub user_to_session: HashMap<ActorId, Session>,
+    pub owner: ActorId,
+    pub importing: bool,
+    pub paused: bool,
+    pub backends: Vec<Backend>,
+    /// Index of the backend tried first for the next game.
+    pub next_backend: u32,
+    pub config: SessionConfig,
+    pub gas_metrics: GasMetrics,
+    pub user_to_session: SessionStore,
+    pub user_stats: BTreeMap<ActorId, UserStats>,
+    pub moderators: BTreeSet<ActorId>,
+    pub word_packs: BTreeMap<String, WordPack>,
+    pub challenges: BTreeMap<ChallengeId, Challenge>,
+    pub next_challenge_id: ChallengeId,
+    /// Sequence number of the next `SessionLogEntry`.
+    pub next_event_seq: u64,
+    pub next_game_id: GameId,
+    /// Version of `SessionConfig::scoring`, bumped by every `SetScoring`.
+    pub scoring_version: u32,
+    /// Wins on each word of each bank, for the rarity bonus.
+    pub word_wins: BTreeMap<(String, String), u32>,
+}
+
+impl GameSessionState {
+    pub fn backend_mut(&mut self, program: ActorId) -> Option<&mut Backend> {
+        self.backends.iter_mut().find(|backend| backend.program == program)
+    }
+
+    /// Picks the backend for a new game in `bank`: the next healthy one in round-robin order
+    /// other than `exclude`, preferring backends known to have the bank.
+    pub fn select_backend(&mut self, bank: &str, block: u32, exclude: Option<ActorId>) -> Option<ActorId> {
+        let count = self.backends.len();
+        let candidates = |has_bank: bool| {
+            (0..count)
+                .map(|offset| (self.next_backend as usize + offset) % count)
+                .find(|&index| {
+                    let backend = &self.backends[index];
+                    backend.is_healthy(block)
+                        && Some(backend.program) != exclude
+                        && (!has_bank || backend.info.banks.iter().any(|name| name == bank))
+                })
+        };
+        // a bank no backend lists may still be known to one, otherwise it replies `UnknownBank`
+        let index = candidates(true).or_else(|| candidates(false))?;
+        self.next_backend = ((index + 1) % count) as u32;
+        Some(self.backends[index].program)
+    }
+
+    /// Finished sessions that have been idle for at least `session_ttl` blocks,
+    /// at most `cleanup_batch` of them.
+    pub fn expired_sessions(&self, block: u32) -> Vec<SessionKey> {
+        self.user_to_session
+            .iter()
+            .filter(|(_, session)| {
+                session.status == SessionStatus::StartGameWaiting
+                    && block >= session.last_active_block.saturating_add(self.config.session_ttl)
+            })
+            .map(|(key, _)| *key)
+            .take(self.config.cleanup_batch as usize)
+            .collect()
+    }
+
+    /// Top players of `bank` by points under the current scoring version, then by wins, ties go
+    /// to the player with fewer games. Flagged users are left out.
+    pub fn leaderboard(&self, bank: &str) -> Vec<LeaderboardEntry> {
+        let mut entries: Vec<LeaderboardEntry> = self.user_stats
+            .iter()
+            .filter(|(_, stats)| !stats.flagged)
+            .filter_map(|(user, stats)| {
+                stats.banks.get(bank).map(|bank_stats| LeaderboardEntry {
+                    user: *user,
+                    points: bank_stats.points.total_for(self.scoring_version),
+                    wins: bank_stats.wins,
+                    games_played: bank_stats.games_played,
+                })
+            })
+            .collect();
+        entries.sort_by(|a, b| {
+            b.points.cmp(&a.points)
+                .then(b.wins.cmp(&a.wins))
+                .then(a.games_played.cmp(&b.games_played))
+                .then(a.user.cmp(&b.user))
+        });
+        entries.truncate(LEADERBOARD_SIZE);
+        entries
+    }
+
+    pub fn leaderboards(&self) -> Vec<(String, Vec<LeaderboardEntry>)> {
+        let banks: BTreeSet<&String> = self.user_stats
+            .values()
+            .flat_map(|stats| stats.banks.keys())
+            .collect();
+        banks.into_iter().map(|bank| (bank.clone(), self.leaderboard(bank))).collect()
+    }
+
+    /// Games of `user` that are still in progress, ordered by game id.
+    pub fn open_games(&self, user: ActorId) -> Vec<Session> {
+        self.user_to_session
+            .user_sessions(user)
+            .filter(|(_, session)| session.is_open())
+            .map(|(_, session)| session.clone())
+            .collect()
+    }
+
+    /// Page of the sessions matching `filter`, ordered by user and game id, and the cursor of the next page.
+    pub fn sessions_page(&self, filter: &SessionFilter, cursor: Option<SessionKey>, limit: u32) -> (Vec<(ActorId, Session)>, Option<SessionKey>) {
+        let limit = limit.clamp(1, MAX_SESSIONS_PAGE) as usize;
+        // one more than the page, to tell whether there is a next one
+        let mut sessions: Vec<(ActorId, Session)> = self.user_to_session
+            .iter_by_key(cursor)
+            .filter(|(_, session)| filter.matches(session))
+            .take(limit + 1)
+            .map(|((user, _), session)| (*user, session.clone()))
+            .collect();
+        let next_cursor = if sessions.len() > limit {
+            sessions.truncate(limit);
+            sessions.last().map(|(user, session)| (*user, session.game_id))
+        } else {
+            None
+        };
+        (sessions, next_cursor)
+    }
 }
 
 #[derive(Debug, Encode, Decode, TypeInfo)]
 #[codec(crate = gstd::codec)]
 #[scale_info(crate = gstd::scale_info)]
 pub struct State {
-    pub wordle_program: ActorId,
+    pub version: u32,
+    pub owner: ActorId,
+    pub importing: bool,
+    pub paused: bool,
+    pub backends: Vec<Backend>,
+    pub config: SessionConfig,
+    pub scoring_version: u32,
+    pub gas_metrics: GasMetrics,
     pub user_sessions: Vec<(ActorId, Session)>,
+    pub user_stats: Vec<(ActorId, UserStats)>,
+    pub leaderboards: Vec<(String, Vec<LeaderboardEntry>)>,
+    pub moderators: Vec<ActorId>,
+    pub word_packs: Vec<WordPackInfo>,
+    pub challenges: Vec<ChallengeInfo>,
+    pub next_event_seq: u64,
 }
 
 impl From<GameSessionState> for State {
     fn from (state: GameSessionState) -> Self {
+        let leaderboards = state.leaderboards();
         let GameSessionState {
-            wordle_program,
+            owner,
+            importing,
+            paused,
+            backends,
+            next_backend: _,
+            config,
+            gas_metrics,
             user_to_session,
+            user_stats,
+            moderators,
+            word_packs,
+            challenges,
+            next_challenge_id: _,
+            next_event_seq,
+            next_game_id: _,
+            scoring_version,
+            word_wins: _,
         } = state;
 
+        // sessions are listed in insertion order, stats by user
         let user_sessions = user_to_session
             .iter()
-            .map(|(user, session)| (*user, session.clone()))
+            .map(|((user, _), session)| (*user, session.clone()))
+            .collect();
+
+        let user_stats = user_stats.into_iter().collect();
+
+        let word_packs = word_packs
+            .into_iter()
+            .map(|(name, pack)| WordPackInfo {
+                name,
+                creator: pack.creator,
+                alphabet: pack.alphabet,
+                size: pack.words.len() as u32,
+                status: pack.status,
+                play_count: pack.play_count,
+            })
             .collect();
 
         Self {
-            wordle_program,
+            version: STATE_VERSION,
+            owner,
+            importing,
+            paused,
+            backends,
+            config,
+            scoring_version,
+            gas_metrics,
             user_sessions,
+            user_stats,
+            leaderboards,
+            moderators: moderators.into_iter().collect(),
+            word_packs,
+            challenges: challenges.iter().map(ChallengeInfo::from).collect(),
+            next_event_seq,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,188 @@
+use gstd::prelude::*;
+use wordle_io::GameId;
+
+use crate::{Session, SessionError, SessionEvent, SessionResult};
+
+/// Where a session is in its lifecycle. Sessions only move between statuses through
+/// `SessionStatus::next`.
+///
+/// The first five variants keep the encoding of the first version of the program.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+pub enum SessionStatus {
+    /// The game is over, or was never started.
+    StartGameWaiting,
+    /// `StartGame` was sent to the session's backend.
+    StartGameSent,
+    /// The game is in progress and waits for the player.
+    CheckWordWaiting,
+    /// `CheckWord` was sent to the session's backend.
+    CheckWordSent,
+    /// Reply to any request, as stored by the first version of the program. Sessions never
+    /// move to it, imported ones are reset to `StartGameWaiting`.
+    ReplyReceived(SessionEvent),
+    /// The answer to `StartGame`, waiting for the woken `StartGame` to handle it.
+    StartGameReplied(SessionEvent),
+    /// The answer to `CheckWord`, waiting for the woken `CheckWord` to handle it.
+    CheckWordReplied(SessionEvent),
+    /// `RevealLetter` was sent to the session's backend.
+    HintSent,
+    /// The answer to `RevealLetter`, waiting for the woken `RequestHint` to handle it.
+    HintReplied(SessionEvent),
+}
+
+/// Something that happens to a session.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionInput {
+    /// `StartGame` was sent again, to another backend.
+    StartResent,
+    /// The Wordle program answered the request the session waits for. Errors, including a
+    /// failed or missing reply, are answers too.
+    Replied(SessionEvent),
+    /// The player's guess was sent to the Wordle program.
+    GuessSent,
+    /// The player's hint request was sent to the Wordle program.
+    HintSent,
+    /// The woken message handled the answer, or gave up waiting, and the game goes on.
+    Resumed,
+    /// The woken message handled an answer that ends the game with this result.
+    Settled(SessionResult),
+    /// The game ended with this result while nothing waits for the Wordle program.
+    Finished(SessionResult),
+    /// The game ended with this result while a message waits for the Wordle program, by a
+    /// timeout or `EmergencyStop`. The waiting message replies `GameOver` once it runs.
+    Interrupted(SessionResult),
+}
+
+impl SessionStatus {
+    /// The session lifecycle in one table: the status a session in this status moves to on
+    /// `input`, or `None` if `input` is not allowed in this status.
+    pub fn next(&self, game_id: GameId, input: &SessionInput) -> Option<SessionStatus> {
+        use SessionInput as Input;
+        use SessionStatus as Status;
+
+        let next = match (self, input) {
+            // starting, possibly on several backends in turn
+            (Status::StartGameSent, Input::Replied(event)) => Status::StartGameReplied(event.clone()),
+            (Status::StartGameSent | Status::StartGameReplied(_), Input::StartResent) => Status::StartGameSent,
+            (Status::StartGameSent | Status::StartGameReplied(_), Input::Resumed) => Status::CheckWordWaiting,
+
+            // playing, with one request to the Wordle program at a time
+            (Status::CheckWordWaiting, Input::GuessSent) => Status::CheckWordSent,
+            (Status::CheckWordWaiting, Input::HintSent) => Status::HintSent,
+            (Status::CheckWordSent, Input::Replied(event)) => Status::CheckWordReplied(event.clone()),
+            (Status::HintSent, Input::Replied(event)) => Status::HintReplied(event.clone()),
+            (
+                Status::CheckWordSent | Status::CheckWordReplied(_) | Status::HintSent | Status::HintReplied(_),
+                Input::Resumed,
+            ) => Status::CheckWordWaiting,
+
+            // ended from outside, the answer the woken message handles becomes `GameOver`
+            (Status::StartGameSent | Status::StartGameReplied(_), Input::Interrupted(result)) => {
+                Status::StartGameReplied(SessionEvent::GameOver { game_id, result: result.clone() })
+            }
+            (Status::CheckWordSent | Status::CheckWordReplied(_), Input::Interrupted(result)) => {
+                Status::CheckWordReplied(SessionEvent::GameOver { game_id, result: result.clone() })
+            }
+            (Status::HintSent | Status::HintReplied(_), Input::Interrupted(result)) => {
+                Status::HintReplied(SessionEvent::GameOver { game_id, result: result.clone() })
+            }
+
+            // over
+            (Status::CheckWordWaiting, Input::Finished(_)) => Status::StartGameWaiting,
+            (
+                Status::StartGameReplied(_) | Status::CheckWordReplied(_) | Status::HintReplied(_),
+                Input::Settled(_),
+            ) => Status::StartGameWaiting,
+
+            _ => return None,
+        };
+        Some(next)
+    }
+
+    /// Whether a message waits for the Wordle program, to be woken by its answer or its timeout.
+    pub fn is_waiting(&self) -> bool {
+        matches!(self, SessionStatus::StartGameSent | SessionStatus::CheckWordSent | SessionStatus::HintSent)
+    }
+}
+
+impl Session {
+    /// Moves the session along `SessionStatus::next`. A session that ends takes the result
+    /// of the input with it.
+    pub fn advance(&mut self, input: SessionInput) -> Result<(), SessionError> {
+        let status = self.status.next(self.game_id, &input).ok_or(SessionError::GameBusy)?;
+        if let SessionInput::Settled(result) | SessionInput::Finished(result) | SessionInput::Interrupted(result) = input {
+            self.result = result;
+        }
+        self.status = status;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUSES: [SessionStatus; 8] = [
+        SessionStatus::StartGameWaiting,
+        SessionStatus::StartGameSent,
+        SessionStatus::CheckWordWaiting,
+        SessionStatus::CheckWordSent,
+        SessionStatus::StartGameReplied(SessionEvent::Paused),
+        SessionStatus::CheckWordReplied(SessionEvent::Paused),
+        SessionStatus::HintSent,
+        SessionStatus::HintReplied(SessionEvent::Paused),
+    ];
+
+    fn run(inputs: &[SessionInput]) -> Option<SessionStatus> {
+        inputs.iter().try_fold(SessionStatus::StartGameSent, |status, input| status.next(0, input))
+    }
+
+    #[test]
+    fn plays_a_game() {
+        let started = SessionInput::Replied(SessionEvent::Paused);
+        let checked = SessionInput::Replied(SessionEvent::Unpaused);
+        assert_eq!(
+            run(&[started.clone(), SessionInput::Resumed, SessionInput::GuessSent, checked.clone()]),
+            Some(SessionStatus::CheckWordReplied(SessionEvent::Unpaused)),
+        );
+        assert_eq!(
+            run(&[started, SessionInput::Resumed, SessionInput::HintSent, checked, SessionInput::Resumed, SessionInput::Finished(SessionResult::Forfeited)]),
+            Some(SessionStatus::StartGameWaiting),
+        );
+    }
+
+    #[test]
+    fn one_request_at_a_time() {
+        // only a game waiting for the player sends anything to the Wordle program
+        for status in STATUSES.iter().filter(|status| **status != SessionStatus::CheckWordWaiting) {
+            assert_eq!(status.next(0, &SessionInput::GuessSent), None);
+            assert_eq!(status.next(0, &SessionInput::HintSent), None);
+        }
+        // answers only go to a request that waits for one
+        for status in STATUSES.iter().filter(|status| !status.is_waiting()) {
+            assert_eq!(status.next(0, &SessionInput::Replied(SessionEvent::Paused)), None);
+        }
+        // and the answer to a guess is never taken for the start of a game
+        let replied = SessionStatus::CheckWordSent.next(0, &SessionInput::Replied(SessionEvent::Paused));
+        assert_eq!(replied, Some(SessionStatus::CheckWordReplied(SessionEvent::Paused)));
+        assert_eq!(SessionStatus::CheckWordWaiting.next(0, &SessionInput::StartResent), None);
+    }
+
+    #[test]
+    fn interrupts_waiting_requests() {
+        let game_over = SessionEvent::GameOver { game_id: 3, result: SessionResult::Void };
+        let interrupted = SessionInput::Interrupted(SessionResult::Void);
+        assert_eq!(SessionStatus::HintSent.next(3, &interrupted), Some(SessionStatus::HintReplied(game_over.clone())));
+        assert_eq!(
+            SessionStatus::CheckWordReplied(SessionEvent::Paused).next(3, &interrupted),
+            Some(SessionStatus::CheckWordReplied(game_over)),
+        );
+        // nothing waits, so the game just ends
+        assert_eq!(SessionStatus::CheckWordWaiting.next(3, &interrupted), None);
+        assert_eq!(SessionStatus::CheckWordWaiting.next(3, &SessionInput::Finished(SessionResult::Void)), Some(SessionStatus::StartGameWaiting));
+        // a finished game takes no more input
+        for input in [SessionInput::Resumed, SessionInput::Finished(SessionResult::Void), interrupted] {
+            assert_eq!(SessionStatus::StartGameWaiting.next(3, &input), None);
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use gstd::{collections::BTreeMap, prelude::*, ActorId, MessageId};
+
+use wordle_io::{Alphabet, DEFAULT_BANK};
+
+use crate::{BankStats, GameMode, LetterState, Points, Session, SessionResult, SessionStatus, UserStats};
+
+/// Version of the state schema exported and imported by the current program.
+pub const STATE_VERSION: u32 = 3;
+
+/// A chunk of exported state, tagged with the schema version it was produced by. Every version
+/// keeps its own copy of the types it was encoded with, so later changes to the live types
+/// can't change how an old chunk decodes.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum StateChunk {
+    V1(StateV1),
+    V2(StateChunkV2),
+    V3(StateChunkV3),
+}
+
+impl StateChunk {
+    pub fn version(&self) -> u32 {
+        match self {
+            StateChunk::V1(_) => 1,
+            StateChunk::V2(_) => 2,
+            StateChunk::V3(_) => 3,
+        }
+    }
+
+    /// Brings the chunk up to `STATE_VERSION`, applying each migration in turn.
+    pub fn migrate(self) -> StateChunkV3 {
+        match self {
+            StateChunk::V1(chunk) => StateChunk::V2(chunk.into()).migrate(),
+            StateChunk::V2(chunk) => chunk.into(),
+            StateChunk::V3(chunk) => chunk,
+        }
+    }
+}
+
+// v1, the state replied by `state()` of the first version of the program
+
+/// The events of v1 that `handle_reply` stores in a session, under their indices in `SessionEvent`.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum SessionEventV1 {
+    #[codec(index = 1)]
+    GameStarted,
+    #[codec(index = 2)]
+    WordChecked { correct_positions: Vec<u8>, contained_in_word: Vec<u8> },
+    #[codec(index = 3)]
+    GameOver { result: SessionResultV1 },
+}
+
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum SessionStatusV1 {
+    StartGameWaiting,
+    StartGameSent,
+    CheckWordWaiting,
+    CheckWordSent,
+    ReplyReceived(SessionEventV1),
+}
+
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum SessionResultV1 {
+    Ongoing,
+    Win,
+    Lose,
+}
+
+/// Session as stored by the first version of the program.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct SessionV1 {
+    pub start_block: u32,
+    pub check_count: u8,
+    pub msg_ids: (MessageId, MessageId),
+    pub status: SessionStatusV1,
+    pub result: SessionResultV1,
+}
+
+/// State replied by `state()` of the first version of the program. A v1 program has
+/// no export action, so its `state()` reply is imported directly, whole or split by users.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct StateV1 {
+    pub wordle_program: ActorId,
+    pub user_sessions: Vec<(ActorId, SessionV1)>,
+}
+
+impl From<SessionStatusV1> for SessionStatusV2 {
+    fn from(status: SessionStatusV1) -> Self {
+        match status {
+            // the reply was meant for a message of the old program, `import_chunk` settles the game
+            SessionStatusV1::StartGameWaiting | SessionStatusV1::ReplyReceived(_) => Self::StartGameWaiting,
+            SessionStatusV1::StartGameSent => Self::StartGameSent,
+            SessionStatusV1::CheckWordWaiting => Self::CheckWordWaiting,
+            SessionStatusV1::CheckWordSent => Self::CheckWordSent,
+        }
+    }
+}
+
+impl From<SessionV1> for SessionV2 {
+    fn from(session: SessionV1) -> Self {
+        let SessionV1 { start_block, check_count, msg_ids, status, result } = session;
+
+        Self { start_block, last_active_block: start_block, check_count, msg_ids, status: status.into(), result }
+    }
+}
+
+impl From<StateV1> for StateChunkV2 {
+    /// v1 kept no stats, so they are rebuilt from the last game of every user.
+    fn from(state: StateV1) -> Self {
+        let mut chunk = StateChunkV2::default();
+        for (user, session) in state.user_sessions {
+            let session: SessionV2 = session.into();
+            let mut stats = UserStatsV2::default();
+            if session.result != SessionResultV1::Ongoing {
+                stats.games_played = 1;
+                stats.wins = (session.result == SessionResultV1::Win) as u32;
+                stats.losses = (session.result == SessionResultV1::Lose) as u32;
+                stats.last_active_block = session.last_active_block;
+            }
+            chunk.user_stats.push((user, stats));
+            chunk.user_sessions.push((user, session));
+        }
+        chunk
+    }
+}
+
+// v2, sessions gained `last_active_block`, and user stats are exported with them
+
+/// `WordleError` as encoded by v2.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum WordleErrorV2 {
+    UnauthorizedSessionProgram,
+    NotOwner,
+    UnsupportedVersion,
+}
+
+/// The errors of v2 that `handle_reply` stores in a session, under their indices in `SessionError`.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum SessionErrorV2 {
+    #[codec(index = 0)]
+    Wordle(WordleErrorV2),
+}
+
+/// The events of v2 that `handle_reply` stores in a session, under their indices in `SessionEvent`.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum SessionEventV2 {
+    #[codec(index = 1)]
+    GameStarted,
+    #[codec(index = 2)]
+    WordChecked { correct_positions: Vec<u8>, contained_in_word: Vec<u8> },
+    #[codec(index = 3)]
+    GameOver { result: SessionResultV1 },
+    #[codec(index = 7)]
+    Error(SessionErrorV2),
+}
+
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub enum SessionStatusV2 {
+    StartGameWaiting,
+    StartGameSent,
+    CheckWordWaiting,
+    CheckWordSent,
+    ReplyReceived(SessionEventV2),
+}
+
+#[derive(Debug, Default, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct UserStatsV2 {
+    pub games_played: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub last_active_block: u32,
+}
+
+#[derive(Debug, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct SessionV2 {
+    pub start_block: u32,
+    pub last_active_block: u32,
+    pub check_count: u8,
+    pub msg_ids: (MessageId, MessageId),
+    pub status: SessionStatusV2,
+    pub result: SessionResultV1,
+}
+
+/// Chunk of v2, sessions gained `last_active_block`, and user stats are exported with them.
+#[derive(Debug, Default, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct StateChunkV2 {
+    pub user_sessions: Vec<(ActorId, SessionV2)>,
+    pub user_stats: Vec<(ActorId, UserStatsV2)>,
+}
+
+impl From<SessionResultV1> for SessionResult {
+    fn from(result: SessionResultV1) -> Self {
+        match result {
+            SessionResultV1::Ongoing => Self::Ongoing,
+            SessionResultV1::Win => Self::Win,
+            SessionResultV1::Lose => Self::Lose,
+        }
+    }
+}
+
+impl From<SessionStatusV2> for SessionStatus {
+    fn from(status: SessionStatusV2) -> Self {
+        match status {
+            // the reply was meant for a message of the old program, `import_chunk` settles the game
+            SessionStatusV2::StartGameWaiting | SessionStatusV2::ReplyReceived(_) => Self::StartGameWaiting,
+            SessionStatusV2::StartGameSent => Self::StartGameSent,
+            SessionStatusV2::CheckWordWaiting => Self::CheckWordWaiting,
+            SessionStatusV2::CheckWordSent => Self::CheckWordSent,
+        }
+    }
+}
+
+impl From<UserStatsV2> for UserStats {
+    fn from(stats: UserStatsV2) -> Self {
+        let UserStatsV2 { games_played, wins, losses, last_active_block } = stats;
+
+        // every game so far was played in the default bank
+        let mut banks = BTreeMap::new();
+        if games_played > 0 {
+            banks.insert(DEFAULT_BANK.to_string(), BankStats { games_played, wins, losses, ..Default::default() });
+        }
+        Self {
+            games_played,
+            wins,
+            losses,
+            timeouts: 0,
+            forfeits: 0,
+            last_active_block,
+            pack_plays: 0,
+            banks,
+            day: 0,
+            games_today: 0,
+            hints_used: 0,
+            points: Points::default(),
+            fast_solves: 0,
+            flagged: false,
+        }
+    }
+}
+
+impl From<SessionV2> for Session {
+    fn from(session: SessionV2) -> Self {
+        let SessionV2 { start_block, last_active_block, check_count, msg_ids, status, result } = session;
+
+        Self {
+            game_id: 0,
+            mode: GameMode::Ranked,
+            backend: ActorId::zero(),
+            start_attempts: 1,
+            start_block,
+            last_active_block,
+            check_count,
+            msg_ids,
+            status: status.into(),
+            result: result.into(),
+            bank: DEFAULT_BANK.to_string(),
+            alphabet: Alphabet::Latin,
+            keyboard: vec![LetterState::Unknown; Alphabet::Latin.letters().len()],
+            challenge: None,
+            known_positions: Vec::new(),
+            hints: Vec::new(),
+            answer: None,
+            score: None,
+            timeout_reservation: None,
+        }
+    }
+}
+
+impl From<StateChunkV2> for StateChunkV3 {
+    fn from(chunk: StateChunkV2) -> Self {
+        Self {
+            user_sessions: chunk.user_sessions.into_iter().map(|(user, session)| (user, session.into())).collect(),
+            user_stats: chunk.user_stats.into_iter().map(|(user, stats)| (user, stats.into())).collect(),
+        }
+    }
+}
+
+/// Chunk of the current version, encoded with the live types.
+#[derive(Debug, Default, Clone, Encode, Decode, PartialEq, TypeInfo)]
+#[codec(crate = gstd::codec)]
+#[scale_info(crate = gstd::scale_info)]
+pub struct StateChunkV3 {
+    pub user_sessions: Vec<(ActorId, Session)>,
+    pub user_stats: Vec<(ActorId, UserStats)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_a_v1_state() {
+        // a won game, laid out the way the first version of the program encoded it
+        let mut bytes = vec![0u8];
+        bytes.extend([1; 32]);
+        bytes.push(1 << 2);
+        bytes.extend([2; 32]);
+        bytes.extend(5u32.to_le_bytes());
+        bytes.push(2);
+        bytes.extend([0; 64]);
+        // `ReplyReceived(GameOver { result: Win })`, then `Win`
+        bytes.extend([4, 3, 1, 1]);
+
+        let chunk = StateChunk::decode(&mut &bytes[..]).expect("v1 state does not decode");
+        assert_eq!(chunk.version(), 1);
+        let chunk = chunk.migrate();
+        let (user, session) = &chunk.user_sessions[0];
+        assert_eq!(*user, ActorId::new([2; 32]));
+        assert_eq!((session.start_block, session.last_active_block, session.check_count), (5, 5, 2));
+        assert_eq!(session.status, SessionStatus::StartGameWaiting);
+        assert_eq!(session.result, SessionResult::Win);
+        let (_, stats) = &chunk.user_stats[0];
+        assert_eq!((stats.games_played, stats.wins, stats.losses), (1, 1, 0));
+        assert_eq!(stats.banks[DEFAULT_BANK].wins, 1);
+    }
+}
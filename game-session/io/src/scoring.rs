@@ -0,0 +1,95 @@
+use gstd::prelude::*;
+
+/// Points formula for finished games. Every `SessionAction::SetScoring` starts a new scoring
+/// version, and points are only ever added up within one version.
+#[derive(Debug, Clone, Copy, Encode, Decode, PartialEq, Eq, TypeInfo)]
+pub struct ScoringConfig {
+    /// Points for any win.
+    pub win_points: u32,
+    /// Points for each attempt a win left unused, attempts spent on hints included.
+    pub attempt_points: u32,
+    /// Bonus for a win in the block the game started, it shrinks to nothing over `speed_blocks`.
+    pub speed_points: u32,
+    pub speed_blocks: u32,
+    /// Bonus for the first win on a word of a bank, divided by one more than the wins on it before.
+    pub rarity_points: u32,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            win_points: 100,
+            attempt_points: 10,
+            speed_points: 50,
+            speed_blocks: 200,
+            rarity_points: 50,
+        }
+    }
+}
+
+/// What a finished game is scored on.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ScoreInput {
+    pub won: bool,
+    pub attempts_left: u8,
+    /// Blocks from the start of the game to its end.
+    pub blocks: u32,
+    /// Points taken off for hints, see `HintCost::ScorePenalty`.
+    pub hint_penalty: u32,
+    /// Wins on the same word of the same bank before this one.
+    pub word_wins: u32,
+}
+
+impl ScoringConfig {
+    /// Points for a finished game, a lost game scores nothing.
+    pub fn points(&self, input: &ScoreInput) -> u32 {
+        if !input.won {
+            return 0;
+        }
+        let speed = match self.speed_blocks {
+            0 => 0,
+            blocks => (self.speed_points as u64 * blocks.saturating_sub(input.blocks) as u64 / blocks as u64) as u32,
+        };
+        let rarity = self.rarity_points / input.word_wins.saturating_add(1);
+        self.win_points
+            .saturating_add(self.attempt_points.saturating_mul(input.attempts_left as u32))
+            .saturating_add(speed)
+            .saturating_add(rarity)
+            .saturating_sub(input.hint_penalty)
+    }
+}
+
+/// Points of a finished game and the scoring version they were computed under.
+#[derive(Debug, Clone, Copy, Encode, Decode, PartialEq, Eq, TypeInfo)]
+pub struct Score {
+    pub version: u32,
+    pub points: u32,
+}
+
+/// Points added up within a single scoring version. A score of a newer version starts
+/// the total over, one of an older version is left out.
+#[derive(Debug, Default, Clone, Copy, Encode, Decode, PartialEq, Eq, TypeInfo)]
+pub struct Points {
+    pub version: u32,
+    pub total: u64,
+}
+
+impl Points {
+    pub fn add(&mut self, score: &Score) {
+        if score.version > self.version {
+            *self = Points { version: score.version, total: 0 };
+        } else if score.version < self.version {
+            return;
+        }
+        self.total += score.points as u64;
+    }
+
+    /// Total under `version`, zero if the points were added up under another one.
+    pub fn total_for(&self, version: u32) -> u64 {
+        if self.version == version {
+            self.total
+        } else {
+            0
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use gstd::{prelude::*, ActorId, collections::BTreeMap};
+use core::ops::Bound;
+use wordle_io::GameId;
+
+use crate::Session;
+
+/// A session is identified by its player and the game id it was started with.
+pub type SessionKey = (ActorId, GameId);
+
+/// Sessions keyed by player and game that remember the order they were first inserted in.
+/// Every key keeps its insertion index until its session is removed, so iterating the store
+/// gives the same order on every node and for every snapshot.
+#[derive(Default, Debug, PartialEq, Clone)]
+pub struct SessionStore {
+    indices: BTreeMap<SessionKey, u64>,
+    sessions: BTreeMap<u64, (SessionKey, Session)>,
+    next_index: u64,
+}
+
+impl SessionStore {
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &SessionKey) -> bool {
+        self.indices.contains_key(key)
+    }
+
+    pub fn index_of(&self, key: &SessionKey) -> Option<u64> {
+        self.indices.get(key).copied()
+    }
+
+    pub fn get(&self, key: &SessionKey) -> Option<&Session> {
+        let index = self.indices.get(key)?;
+        self.sessions.get(index).map(|(_, session)| session)
+    }
+
+    pub fn get_mut(&mut self, key: &SessionKey) -> Option<&mut Session> {
+        let index = self.indices.get(key)?;
+        self.sessions.get_mut(index).map(|(_, session)| session)
+    }
+
+    /// Inserts the session under `key`, replacing the previous one in place.
+    pub fn insert(&mut self, key: SessionKey, session: Session) -> Option<Session> {
+        if let Some(index) = self.indices.get(&key) {
+            return self.sessions.insert(*index, (key, session)).map(|(_, session)| session);
+        }
+        let index = self.next_index;
+        self.next_index += 1;
+        self.indices.insert(key, index);
+        self.sessions.insert(index, (key, session));
+        None
+    }
+
+    pub fn remove(&mut self, key: &SessionKey) -> Option<Session> {
+        let index = self.indices.remove(key)?;
+        self.sessions.remove(&index).map(|(_, session)| session)
+    }
+
+    /// Sessions in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&SessionKey, &Session)> {
+        self.sessions.values().map(|(key, session)| (key, session))
+    }
+
+    /// Sessions in insertion order.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&SessionKey, &mut Session)> {
+        self.sessions.values_mut().map(|(key, session)| (&*key, session))
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Session> {
+        self.sessions.values_mut().map(|(_, session)| session)
+    }
+
+    /// Keys in ascending order.
+    pub fn keys(&self) -> impl Iterator<Item = &SessionKey> {
+        self.indices.keys()
+    }
+
+    /// Sessions of `user` ordered by game id.
+    pub fn user_sessions(&self, user: ActorId) -> impl Iterator<Item = (&SessionKey, &Session)> {
+        self.indices
+            .range((user, GameId::MIN)..=(user, GameId::MAX))
+            .map(move |(key, index)| (key, &self.sessions[index].1))
+    }
+
+    /// Sessions ordered by key, starting after `cursor`.
+    pub fn iter_by_key(&self, cursor: Option<SessionKey>) -> impl Iterator<Item = (&SessionKey, &Session)> {
+        let start = cursor.map_or(Bound::Unbounded, Bound::Excluded);
+        self.indices
+            .range((start, Bound::Unbounded))
+            .map(move |(key, index)| (key, &self.sessions[index].1))
+    }
+}
@@ -1,207 +1,1478 @@
 #![no_std]
-use gstd::{exec, msg, prelude::*, debug, ActorId, collections::HashMap, MessageId};
-use wordle_io::{Action, Event};
+use gstd::{exec, msg, prelude::*, debug, errors::ReplyCode, ActorId, collections::{BTreeMap, BTreeSet}, MessageId, ReservationId};
+use wordle_io::{Action, Alphabet, Event, Feature, GameId, WordleInfo, DEFAULT_BANK, PROTOCOL_VERSION, WORD_LENGTH};
+use wordle_engine::{Feedback, Outcome, Progress, MAX_ATTEMPTS};
 use game_session_io::*;
 
 static mut GAME_SESSION_STATE: Option<GameSessionState> = None;
-const MAX_CHECK_COUNT: u8 = 6;
+/// Handshakes in progress, by the message waiting for them.
+static mut HANDSHAKES: Option<BTreeMap<MessageId, Handshake>> = None;
 const CHECK_GAME_STATUS_DELAY: u32 = 200;
+const TIMEOUT_RESERVATION_GAS: u64 = 5_000_000_000;
+/// Backends asked to start a game before giving up.
+const MAX_START_ATTEMPTS: u8 = 3;
+// the reservation has to outlive the waits for wordle's replies as well as the timeout delay
+const TIMEOUT_RESERVATION_BLOCKS: u32 = CHECK_GAME_STATUS_DELAY + BACKEND_REPLY_TIMEOUT * MAX_START_ATTEMPTS as u32 + 40;
+
+/// `GetInfo` sent to a Wordle program, then the answer the waiting message is woken with.
+enum Handshake {
+    Sent { program: ActorId, msg_id: MessageId },
+    /// `None` if the Wordle program failed to answer with `Event::Info`.
+    Received(Option<WordleInfo>),
+}
+
+/// Checks that the Wordle program speaks the protocol this program was built against.
+fn check_wordle_info(info: &WordleInfo) -> Result<(), String> {
+    if info.protocol_version != PROTOCOL_VERSION {
+        return Err(format!("protocol version {}, expected {}", info.protocol_version, PROTOCOL_VERSION));
+    }
+    if !info.word_lengths.contains(&(WORD_LENGTH as u8)) {
+        return Err(format!("no {}-letter words", WORD_LENGTH));
+    }
+    if !info.banks.iter().any(|bank| bank == DEFAULT_BANK) {
+        return Err(format!("no `{}` bank", DEFAULT_BANK));
+    }
+    Ok(())
+}
+
+/// Asks `program` what it supports on behalf of the current message. The first run sends
+/// `GetInfo` and waits, the run woken by `handle_reply` or the timeout returns the answer.
+fn handshake(program: ActorId) -> Result<WordleInfo, String> {
+    let handshakes = unsafe { HANDSHAKES.get_or_insert_with(BTreeMap::new) };
+    match handshakes.remove(&msg::id()) {
+        None => {
+            let msg_id = msg::send(program, Action::GetInfo, 0)
+                .expect("handshake: error in sending `Action::GetInfo`");
+            handshakes.insert(msg::id(), Handshake::Sent { program, msg_id });
+            debug!("handshake: `GetInfo` wait");
+            exec::wait_for(BACKEND_REPLY_TIMEOUT);
+        },
+        Some(Handshake::Received(Some(info))) => check_wordle_info(&info).map(|_| info),
+        Some(Handshake::Received(None)) => Err("no answer to `GetInfo`".to_string()),
+        Some(Handshake::Sent { .. }) => Err("`GetInfo` timed out".to_string()),
+    }
+}
+
+/// Whether a message is waiting for the answer of `program` to `GetInfo`.
+fn handshake_pending(program: ActorId) -> bool {
+    let handshakes = unsafe { HANDSHAKES.get_or_insert_with(BTreeMap::new) };
+    handshakes.values().any(|handshake| matches!(handshake, Handshake::Sent { program: sent_to, .. } if *sent_to == program))
+}
 
 #[no_mangle]
 extern "C" fn init() {
-    let wordle_program = msg::load().expect("Unable to decode init");
+    let GameSessionInit { wordle_program, config, import } = msg::load().expect("Unable to decode init");
     debug!("wordle program id: {:x?}", wordle_program);
+    if let Err(error) = config.validate() {
+        panic!("init: invalid config: {:?}", error);
+    }
+
+    // the Wordle program is asked what it supports first
+    let info = match handshake(wordle_program) {
+        Ok(info) => info,
+        Err(reason) => panic!("init: incompatible Wordle program: {}", reason),
+    };
 
+    let mut state = GameSessionState {
+        owner: msg::source(),
+        importing: import.is_some(),
+        paused: false,
+        backends: vec![Backend::new(wordle_program, info)],
+        next_backend: 0,
+        config,
+        gas_metrics: GasMetrics::default(),
+        user_to_session: SessionStore::default(),
+        user_stats: BTreeMap::new(),
+        moderators: BTreeSet::new(),
+        word_packs: BTreeMap::new(),
+        challenges: BTreeMap::new(),
+        next_challenge_id: 0,
+        next_event_seq: 0,
+        next_game_id: 0,
+        scoring_version: 1,
+        word_wins: BTreeMap::new(),
+    };
+    if let Some(chunk) = import {
+        if let Err(error) = import_chunk(&mut state, chunk) {
+            panic!("init: failed to import the state: {:?}", error);
+        }
+    }
     unsafe {
-        GAME_SESSION_STATE = Some(GameSessionState {
-            wordle_program: wordle_program,
-            user_to_session: HashMap::new(),
-        });
+        GAME_SESSION_STATE = Some(state);
     }
+    schedule_cleanup(config.cleanup_interval);
     msg::reply(SessionEvent::Initialized, 0).expect("Unable to reply init");
 }
 
-fn start_game() {
+fn start_game(bank: Option<String>, challenge_id: Option<ChallengeId>, mode: GameMode) {
     let state = unsafe {GAME_SESSION_STATE.as_mut().expect("GAME_SESSION_STATE is not initialized")};
     let user = msg::source();
-    if !state.user_to_session.contains_key(&user) {
-        state.user_to_session.insert(user, Session {
-            start_block: 0,
-            check_count: 0,
-            msg_ids: (MessageId::zero(), MessageId::zero()),
-            status: SessionStatus::StartGameWaiting,
-            result: SessionResult::Ongoing,
-        });
+    // woken by `handle_reply` once the Wordle program has answered
+    let started = state.user_to_session
+        .user_sessions(user)
+        .find(|(_, session)| session.msg_ids.1 == msg::id())
+        .map(|(key, _)| *key);
+    if let Some(key) = started {
+        game_started(state, key);
+        return;
     }
 
-    let session: &mut Session = state.user_to_session.get_mut(&user).unwrap();
+    if state.importing {
+        reply_error(SessionError::ImportInProgress);
+        return;
+    }
+    // an accepted challenge is played on the challenger's word and bank
+    let (bank, word) = match challenge_id {
+        Some(challenge_id) => {
+            let challenge = state.challenges.get(&challenge_id)
+                .filter(|challenge| challenge.friend == user && challenge.status == ChallengeStatus::Pending);
+            let accepting = state.user_to_session
+                .user_sessions(user)
+                .any(|(_, session)| session.challenge == Some(challenge_id));
+            let Some(challenge) = challenge.filter(|_| !accepting) else {
+                reply_error(SessionError::UnknownChallenge);
+                return;
+            };
+            (challenge.bank.clone(), Some(challenge.word.clone()))
+        },
+        None => (bank.unwrap_or_else(|| DEFAULT_BANK.to_string()), None),
+    };
+    // games in progress can still finish, only new ones are blocked
+    if state.paused {
+        reply_error(SessionError::Paused);
+        return;
+    }
+    // practice games don't count, so only ranked ones are capped
+    if !mode.is_practice() {
+        let max_games = state.config.anti_abuse.max_games_per_day;
+        let games_today = state.user_stats.get(&user).map_or(0, |stats| stats.games_on_day_of(exec::block_height()));
+        // games still waiting for wordle are only counted once it replies, but they take a slot already
+        let pending_starts = state.user_to_session
+            .user_sessions(user)
+            .filter(|(_, session)| !session.mode.is_practice())
+            .filter(|(_, session)| matches!(session.status, SessionStatus::StartGameSent | SessionStatus::StartGameReplied(_)))
+            .count() as u32;
+        if max_games > 0 && games_today + pending_starts >= max_games {
+            reply_error(SessionError::DailyGameLimit);
+            return;
+        }
+        let open_games = state.user_to_session
+            .user_sessions(user)
+            .filter(|(_, session)| session.is_open() && !session.mode.is_practice())
+            .count();
+        if open_games >= MAX_OPEN_GAMES {
+            reply_error(SessionError::TooManyOpenGames);
+            return;
+        }
+    }
+    let Some(backend) = state.select_backend(&bank, exec::block_height(), None) else {
+        reply_error(SessionError::BackendUnavailable);
+        return;
+    };
+    let Ok(reservation_id) = ReservationId::reserve(TIMEOUT_RESERVATION_GAS, TIMEOUT_RESERVATION_BLOCKS) else {
+        reply_error(SessionError::NotEnoughGasForTimeout);
+        return;
+    };
+    state.gas_metrics.reserved_gas += TIMEOUT_RESERVATION_GAS;
+    state.gas_metrics.active_reservations += 1;
+    if mode.is_practice() {
+        end_practice_games(state, user);
+    }
+
+    let game_id = state.next_game_id;
+    state.next_game_id += 1;
+    let msg_id: gstd::MessageId = msg::send(backend, Action::StartGame { user, game_id, bank: bank.clone(), word }, 0)
+        .expect("start_game: error in sending `Action::StartGame`");
+    state.user_to_session.insert((user, game_id), Session {
+        game_id,
+        mode,
+        backend,
+        start_attempts: 1,
+        start_block: 0,
+        last_active_block: exec::block_height(),
+        check_count: 0,
+        msg_ids: (msg_id, msg::id()),
+        status: SessionStatus::StartGameSent,
+        result: SessionResult::Ongoing,
+        bank,
+        alphabet: Alphabet::Latin,
+        keyboard: empty_keyboard(Alphabet::Latin),
+        challenge: challenge_id,
+        known_positions: Vec::new(),
+        hints: Vec::new(),
+        answer: None,
+        score: None,
+        timeout_reservation: Some(reservation_id),
+    });
+
+    debug!("start_game: `StartGame` wait");
+    exec::wait_for(BACKEND_REPLY_TIMEOUT);
+}
+
+/// Second half of `start_game`, run once the Wordle program has replied to `StartGame`
+/// or has not replied in time.
+fn game_started(state: &mut GameSessionState, key: SessionKey) {
+    let (user, game_id) = key;
+    let session = state.user_to_session.get_mut(&key).unwrap();
+    // still waiting, so the wait timed out
+    if session.status.is_waiting() {
+        debug!("start_game: backend {:x?} did not reply in time", session.backend);
+        session.advance(SessionInput::Replied(SessionEvent::Error(SessionError::BackendUnavailable)))
+            .expect("start_game: wrong status");
+        let backend = session.backend;
+        if let Some(backend) = state.backend_mut(backend) {
+            backend.record_failure(exec::block_height());
+        }
+    }
+
+    let session = state.user_to_session.get(&key).unwrap();
     debug!("start_game: status is {:x?}", session.status);
-    match &session.status {
-        SessionStatus::StartGameWaiting | SessionStatus::CheckWordWaiting => {
-            let msg_id: gstd::MessageId = msg::send(state.wordle_program, Action::StartGame { user }, 0)
+    let SessionStatus::StartGameReplied(recv_event) = session.status.clone() else {
+        panic!("start_game: wrong status");
+    };
+    // the game may still start on another backend
+    let failed = matches!(&recv_event, SessionEvent::Error(error) if error.is_backend_failure());
+    if failed && session.start_attempts < MAX_START_ATTEMPTS {
+        let (backend, bank, challenge_id) = (session.backend, session.bank.clone(), session.challenge);
+        if let Some(next) = state.select_backend(&bank, exec::block_height(), Some(backend)) {
+            let word = challenge_id.and_then(|challenge_id| state.challenges.get(&challenge_id)).map(|challenge| challenge.word.clone());
+            let msg_id = msg::send(next, Action::StartGame { user, game_id, bank, word }, 0)
                 .expect("start_game: error in sending `Action::StartGame`");
-            session.msg_ids = (msg_id, msg::id());
-            session.status = SessionStatus::StartGameSent;
-
-            debug!("start_game: `StartGame` wait");
-            exec::wait();
-        },
-        SessionStatus::ReplyReceived(recv_event) => {
-            if let SessionEvent::GameStarted = recv_event {
-                session.start_block = exec::block_height();
-                session.check_count = 0;
-                session.msg_ids = (MessageId::zero(), MessageId::zero());
-                session.status = SessionStatus::CheckWordWaiting;
-                session.result = SessionResult::Ongoing;
-                msg::reply(SessionEvent::GameStarted , 0).expect("Error in sending `GameStarted` reply");
-                debug!("start_game: send delayed message, program={:x?}, user={:x?}", exec::program_id(), user);
-                msg::send_delayed(exec::program_id(), SessionAction::CheckGameStatus { user }, 0, CHECK_GAME_STATUS_DELAY)
-                    .expect("start_game: error in sending `SessionAction::CheckGameStatus`");
-            } else {
-                panic!("start_game: invalid received event");
+            let session = state.user_to_session.get_mut(&key).unwrap();
+            session.advance(SessionInput::StartResent).expect("start_game: wrong status");
+            session.backend = next;
+            session.start_attempts += 1;
+            session.msg_ids.0 = msg_id;
+
+            debug!("start_game: `StartGame` wait on another backend");
+            exec::wait_for(BACKEND_REPLY_TIMEOUT);
+        }
+    }
+
+    let session: &mut Session = state.user_to_session.get_mut(&key).unwrap();
+    match recv_event {
+        SessionEvent::GameStarted { alphabet, .. } => {
+            session.start_block = exec::block_height();
+            session.last_active_block = session.start_block;
+            session.check_count = 0;
+            if !session.mode.is_practice() {
+                state.user_stats.entry(user).or_default().count_game(session.start_block);
+                if let Some(pack) = state.word_packs.get_mut(&session.bank).filter(|pack| pack.status == WordPackStatus::Approved) {
+                    pack.play_count += 1;
+                    state.user_stats.entry(pack.creator).or_default().pack_plays += 1;
+                }
             }
+            session.alphabet = alphabet;
+            session.keyboard = empty_keyboard(alphabet);
+            if let Some(challenge) = session.challenge.and_then(|challenge_id| state.challenges.get_mut(&challenge_id)) {
+                challenge.status = ChallengeStatus::Accepted;
+            }
+            session.msg_ids = (MessageId::zero(), MessageId::zero());
+            session.advance(SessionInput::Resumed).expect("start_game: wrong status");
+            msg::reply(SessionEvent::GameStarted { game_id, alphabet }, 0).expect("Error in sending `GameStarted` reply");
+            let event = SessionLogEvent::GameStarted { bank: session.bank.clone(), challenge: session.challenge, mode: session.mode };
+            log_event(&mut state.next_event_seq, state.config.indexer, key, event);
+            debug!("start_game: send delayed message, program={:x?}, user={:x?}", exec::program_id(), user);
+            msg::send_delayed(exec::program_id(), SessionAction::CheckGameStatus { user, game_id }, 0, CHECK_GAME_STATUS_DELAY)
+                .expect("start_game: error in sending `SessionAction::CheckGameStatus`");
+        },
+        SessionEvent::Error(error) => {
+            // the game never started, so there is nothing to keep
+            release_timeout_reservation(&mut state.gas_metrics, session);
+            state.user_to_session.remove(&key);
+            msg::reply(SessionEvent::Error(error), 0).expect("start_game: error in replying `SessionEvent::Error`");
         },
-        _ => panic!("start_game: wrong status"),
+        event @ SessionEvent::GameOver { .. } => {
+            // voided by `EmergencyStop` while waiting for wordle
+            session.advance(SessionInput::Settled(session.result.clone())).expect("start_game: wrong status");
+            release_timeout_reservation(&mut state.gas_metrics, session);
+            msg::reply(event, 0).expect("start_game: error in replying `GameOver`");
+        },
+        _ => panic!("start_game: invalid received event"),
     }
 }
 
-fn check_word(word: String) {
+fn check_word(game_id: GameId, word: String) {
     let state = unsafe {GAME_SESSION_STATE.as_mut().expect("check_word: GAME_SESSION_STATE is not initialized")};
     let user = msg::source();
-    if !state.user_to_session.contains_key(&user) {
-        panic!("check_word: non-existing user");    
+    let key = (user, game_id);
+    if state.importing {
+        reply_error(SessionError::ImportInProgress);
+        return;
     }
-
-    let session: &mut Session = state.user_to_session.get_mut(&user).unwrap();
+    let Some(session) = state.user_to_session.get_mut(&key) else {
+        reply_error(SessionError::NoGameInProgress);
+        return;
+    };
     debug!("check_word: status is {:x?}", session.status);
+    // guesses are compared in their normalized form, the alphabet comes from the word bank
+    let word = match session.alphabet.normalize(&word) {
+        Ok(word) => word,
+        Err(error) => {
+            reply_error(SessionError::Wordle(error));
+            return;
+        },
+    };
 
-    match &session.status {
-        SessionStatus::CheckWordWaiting => {
-            if word.len() != 5 || !word.chars().all(|c| c.is_lowercase()) {
-                panic!("check_word: invalid word");
-            }
-            
-            session.check_count += 1;
-            if session.check_count > MAX_CHECK_COUNT || exec::block_height() > session.start_block + CHECK_GAME_STATUS_DELAY {
-                session.status = SessionStatus::StartGameWaiting;
-                session.result = SessionResult::Lose;
-                msg::reply(SessionEvent::GameOver { result: SessionResult::Lose }, 0)
-                    .expect("check_word: error in replying `SessionEvent::GameOver`");
+    // any run but the one woken for its own `CheckWord` is a new guess
+    if session.msg_ids.1 != msg::id() {
+        if !session.is_open() {
+            reply_error(SessionError::NoGameInProgress);
+            return;
+        }
+        if session.status.next(game_id, &SessionInput::GuessSent).is_none() {
+            reply_error(SessionError::GameBusy);
+            return;
+        }
+        if exec::block_height() < session.last_active_block.saturating_add(state.config.anti_abuse.min_guess_interval) {
+            reply_error(SessionError::GuessTooSoon);
+            return;
+        }
+        session.check_count = session.check_count.saturating_add(1);
+        session.last_active_block = exec::block_height();
+        let timed_out = exec::block_height() > session.start_block + CHECK_GAME_STATUS_DELAY;
+        let out_of_guesses = session.check_count > MAX_ATTEMPTS && !session.mode.unlimited_guesses();
+        if out_of_guesses || timed_out {
+            let (result, event) = if timed_out {
+                (SessionResult::TimedOut, SessionLogEvent::Timeout)
             } else {
-                let msg_id = msg::send(state.wordle_program, Action::CheckWord { user, word }, 0)
-                    .expect("check_word: error in sending `Action::CheckWord`");
-                session.msg_ids = (msg_id, msg::id());
-                session.status = SessionStatus::CheckWordSent;
+                (SessionResult::Lose, SessionLogEvent::Lose)
+            };
+            session.advance(SessionInput::Finished(result.clone())).expect("check_word: wrong status");
+            finish_game(state, key, event);
+            msg::reply(SessionEvent::GameOver { game_id, result }, 0)
+                .expect("check_word: error in replying `SessionEvent::GameOver`");
+        } else {
+            let msg_id = msg::send(session.backend, Action::CheckWord { user, game_id, word }, 0)
+                .expect("check_word: error in sending `Action::CheckWord`");
+            session.msg_ids = (msg_id, msg::id());
+            session.advance(SessionInput::GuessSent).expect("check_word: wrong status");
 
-                debug!("check_word: `CheckWord` wait");
-                exec::wait();
-            }
-        },
-        SessionStatus::ReplyReceived(recv_event) => {
-            if let SessionEvent::WordChecked { correct_positions, contained_in_word } = recv_event {
-
-                session.msg_ids = (MessageId::zero(), MessageId::zero());
-                if correct_positions.len() == 5 {
-                    session.status = SessionStatus::StartGameWaiting;
-                    session.result = SessionResult::Win;
-                    msg::reply(SessionEvent::GameOver { result: SessionResult::Win, }, 0)
-                        .expect("check_word: error in replying `GameOver(Win)`");
-                } else if session.check_count >= 6 {
-                    session.status = SessionStatus::StartGameWaiting;
-                    session.result = SessionResult::Lose;
-                    msg::reply(SessionEvent::GameOver { result: SessionResult::Lose }, 0)
-                        .expect("check_word: error in replying `GameOver(Lose)`");
-                } else {
-                    let event = SessionEvent::WordChecked {
-                        correct_positions: correct_positions.to_vec(),
-                        contained_in_word: contained_in_word.to_vec(),
-                    };
-                    session.status = SessionStatus::CheckWordWaiting;
-                    session.result = SessionResult::Ongoing;
-                    msg::reply(event, 0).expect("check_word: error in replying `WordChecked`");
+            debug!("check_word: `CheckWord` wait");
+            exec::wait_for(BACKEND_REPLY_TIMEOUT);
+        }
+        return;
+    }
+
+    // the guess was not checked either way, so it does not use up an attempt
+    session.msg_ids = (MessageId::zero(), MessageId::zero());
+    if session.status.is_waiting() {
+        // the game lives on its backend, so the guess can't go anywhere else
+        debug!("check_word: backend {:x?} did not reply in time", session.backend);
+        session.check_count -= 1;
+        session.advance(SessionInput::Resumed).expect("check_word: wrong status");
+        let backend = session.backend;
+        if let Some(backend) = state.backend_mut(backend) {
+            backend.record_failure(exec::block_height());
+        }
+        reply_error(SessionError::BackendUnavailable);
+        return;
+    }
+    let SessionStatus::CheckWordReplied(recv_event) = session.status.clone() else {
+        panic!("check_word: wrong status");
+    };
+    match recv_event {
+        SessionEvent::WordChecked { correct_positions, contained_in_word, .. } => {
+            update_keyboard(&mut session.keyboard, session.alphabet, &word, &correct_positions, &contained_in_word);
+            for position in &correct_positions {
+                if !session.known_positions.contains(position) {
+                    session.known_positions.push(*position);
                 }
+            }
+            let guess = SessionLogEvent::Guess {
+                word: word.clone(),
+                correct_positions: correct_positions.clone(),
+                contained_in_word: contained_in_word.clone(),
+            };
+            log_event(&mut state.next_event_seq, state.config.indexer, key, guess);
+            session.last_active_block = exec::block_height();
+            let feedback = Feedback {
+                correct_positions: correct_positions.clone(),
+                contained_in_word: contained_in_word.clone(),
+            };
+            // `check_count` already counts this guess
+            let outcome = if session.mode.unlimited_guesses() {
+                if feedback.is_win() { Outcome::Win } else { Outcome::Ongoing }
             } else {
-                panic!("check_word: invalid ReplyReceived event");
+                Progress::resume(session.check_count - 1).apply(&feedback)
+                    .expect("check_word: guess after the game is over")
+            };
+            if outcome == Outcome::Win {
+                session.answer = Some(word.clone());
+                session.advance(SessionInput::Settled(SessionResult::Win)).expect("check_word: wrong status");
+                finish_game(state, key, SessionLogEvent::Win);
+                msg::reply(SessionEvent::GameOver { game_id, result: SessionResult::Win, }, 0)
+                    .expect("check_word: error in replying `GameOver(Win)`");
+            } else if outcome == Outcome::Lose {
+                session.advance(SessionInput::Settled(SessionResult::Lose)).expect("check_word: wrong status");
+                finish_game(state, key, SessionLogEvent::Lose);
+                msg::reply(SessionEvent::GameOver { game_id, result: SessionResult::Lose }, 0)
+                    .expect("check_word: error in replying `GameOver(Lose)`");
+            } else {
+                let event = SessionEvent::WordChecked {
+                    game_id,
+                    correct_positions,
+                    contained_in_word,
+                    keyboard: session.keyboard.clone(),
+                };
+                session.advance(SessionInput::Resumed).expect("check_word: wrong status");
+                msg::reply(event, 0).expect("check_word: error in replying `WordChecked`");
             }
         },
-        _ => panic!("check_word: wrong status"),
+        SessionEvent::Error(error) => {
+            session.check_count -= 1;
+            session.advance(SessionInput::Resumed).expect("check_word: wrong status");
+            msg::reply(SessionEvent::Error(error), 0).expect("check_word: error in replying `SessionEvent::Error`");
+        },
+        event @ SessionEvent::GameOver { .. } => {
+            // ended by a timeout or `EmergencyStop` while waiting for wordle
+            session.advance(SessionInput::Settled(session.result.clone())).expect("check_word: wrong status");
+            msg::reply(event, 0).expect("check_word: error in replying `GameOver`");
+        },
+        _ => panic!("check_word: invalid received event"),
     }
 }
 
-fn check_game_status(user: &ActorId) {
+fn check_game_status(user: ActorId, game_id: GameId) {
     debug!("check_game_status");
     let state = unsafe {GAME_SESSION_STATE.as_mut()
         .expect("check_game_status: GAME_SESSION_STATE is not initialized")};
-    if !state.user_to_session.contains_key(user) {
-        panic!("check_game_status: non-existing user");
+    if msg::source() != exec::program_id() {
+        panic!("check_game_status: only the program itself can check the game status");
     }
+    let key = (user, game_id);
+    // the session may already have been compacted by `cleanup`
+    let Some(session) = state.user_to_session.get_mut(&key) else {
+        debug!("check_game_status: non-existing game");
+        return;
+    };
 
-    let session: &mut Session = state.user_to_session.get_mut(user).unwrap();
     debug!("check_game_status: block_height={}, start_block={}", exec::block_height(), session.start_block);
-    if exec::block_height() >= session.start_block + CHECK_GAME_STATUS_DELAY && session.result == SessionResult::Ongoing {
-        session.result = SessionResult::Lose;
-        session.status = SessionStatus::StartGameWaiting;
-        msg::send(*user, SessionEvent::GameOver { result: SessionResult::Lose, }, 0)
-            .expect("check_game_status: error in sending `GameOver(Lose)`");
+    if exec::block_height() < session.start_block + CHECK_GAME_STATUS_DELAY || !session.is_open() {
+        return;
+    }
+    session.last_active_block = exec::block_height();
+    let game_over = SessionEvent::GameOver { game_id, result: SessionResult::TimedOut };
+    if session.advance(SessionInput::Finished(SessionResult::TimedOut)).is_ok() {
+        // the gas reserved when the game was requested pays for telling the player
+        match take_timeout_reservation(&mut state.gas_metrics, session) {
+            Some(reservation_id) => {
+                state.gas_metrics.spent_gas += TIMEOUT_RESERVATION_GAS;
+                msg::send_from_reservation(reservation_id, user, game_over, 0)
+            },
+            None => msg::send(user, game_over, 0),
+        }
+        .expect("check_game_status: error in sending `GameOver(TimedOut)`");
+    } else {
+        // a message waits for wordle, it replies `GameOver` itself
+        let waiting = session.status.is_waiting();
+        session.advance(SessionInput::Interrupted(SessionResult::TimedOut)).expect("check_game_status: wrong status");
+        session.msg_ids.0 = MessageId::zero();
+        if waiting {
+            exec::wake(session.msg_ids.1).expect("Failed to wake message");
+        }
+    }
+    finish_game(state, key, SessionLogEvent::Timeout);
+}
+
+/// Takes the timeout reservation off `session`, it no longer counts as held.
+fn take_timeout_reservation(gas_metrics: &mut GasMetrics, session: &mut Session) -> Option<ReservationId> {
+    let reservation_id = session.timeout_reservation.take()?;
+    gas_metrics.reserved_gas = gas_metrics.reserved_gas.saturating_sub(TIMEOUT_RESERVATION_GAS);
+    gas_metrics.active_reservations = gas_metrics.active_reservations.saturating_sub(1);
+    Some(reservation_id)
+}
+
+/// Returns the timeout reservation of a game that ended before its timeout.
+fn release_timeout_reservation(gas_metrics: &mut GasMetrics, session: &mut Session) {
+    if let Some(reservation_id) = take_timeout_reservation(gas_metrics, session) {
+        if let Err(error) = reservation_id.unreserve() {
+            debug!("release_timeout_reservation: failed to unreserve: {:?}", error);
+        }
+    }
+}
+
+fn forfeit(game_id: GameId) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("forfeit: GAME_SESSION_STATE is not initialized")};
+    let key = (msg::source(), game_id);
+    let Some(session) = state.user_to_session.get_mut(&key).filter(|session| session.is_open()) else {
+        reply_error(SessionError::NoGameInProgress);
+        return;
+    };
+    // a request waiting for wordle's reply has to finish first
+    if let Err(error) = session.advance(SessionInput::Finished(SessionResult::Forfeited)) {
+        reply_error(error);
+        return;
+    }
+
+    session.last_active_block = exec::block_height();
+    finish_game(state, key, SessionLogEvent::Forfeit);
+    msg::reply(SessionEvent::GameOver { game_id, result: SessionResult::Forfeited }, 0)
+        .expect("forfeit: error in replying `GameOver(Forfeited)`");
+}
+
+fn request_hint(game_id: GameId) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("request_hint: GAME_SESSION_STATE is not initialized")};
+    let user = msg::source();
+    let key = (user, game_id);
+    if state.importing {
+        reply_hint_error(SessionError::ImportInProgress);
+        return;
+    }
+    let Some(session) = state.user_to_session.get_mut(&key) else {
+        reply_hint_error(SessionError::NoGameInProgress);
+        return;
+    };
+
+    // any run but the one woken for its own `RequestHint` is a new request
+    if session.msg_ids.1 != msg::id() {
+        if !session.is_open() {
+            reply_hint_error(SessionError::NoGameInProgress);
+            return;
+        }
+        if session.status.next(game_id, &SessionInput::HintSent).is_none() {
+            reply_hint_error(SessionError::GameBusy);
+            return;
+        }
+        match state.config.hint_cost {
+            HintCost::Attempt if !session.mode.unlimited_guesses() && session.check_count + 1 >= MAX_ATTEMPTS => {
+                reply_hint_error(SessionError::NoAttemptsLeft);
+                return;
+            },
+            HintCost::Fee(fee) if msg::value() < fee => {
+                reply_hint_error(SessionError::HintFeeTooLow);
+                return;
+            },
+            _ => {},
+        }
+        let supported = state.backends
+            .iter()
+            .any(|backend| backend.program == session.backend && backend.info.features.contains(&Feature::RevealLetter));
+        if !supported {
+            reply_hint_error(SessionError::NotSupported);
+            return;
+        }
+        let action = Action::RevealLetter { user, game_id, known: session.known_positions.clone() };
+        let msg_id = msg::send(session.backend, action, 0)
+            .expect("request_hint: error in sending `Action::RevealLetter`");
+        session.msg_ids = (msg_id, msg::id());
+        session.advance(SessionInput::HintSent).expect("request_hint: wrong status");
+
+        debug!("request_hint: `RevealLetter` wait");
+        exec::wait_for(BACKEND_REPLY_TIMEOUT);
+    }
+
+    session.msg_ids = (MessageId::zero(), MessageId::zero());
+    if session.status.is_waiting() {
+        debug!("request_hint: backend {:x?} did not reply in time", session.backend);
+        session.advance(SessionInput::Resumed).expect("request_hint: wrong status");
+        let backend = session.backend;
+        if let Some(backend) = state.backend_mut(backend) {
+            backend.record_failure(exec::block_height());
+        }
+        reply_hint_error(SessionError::BackendUnavailable);
+        return;
+    }
+    let SessionStatus::HintReplied(recv_event) = session.status.clone() else {
+        panic!("request_hint: wrong status");
+    };
+    match recv_event {
+        SessionEvent::HintRevealed { position, letter, .. } => {
+            session.advance(SessionInput::Resumed).expect("request_hint: wrong status");
+            session.known_positions.push(position);
+            session.hints.push((position, letter.clone()));
+            if let Some(index) = letter.chars().next().and_then(|letter| session.alphabet.index_of(letter)) {
+                session.keyboard[index] = LetterState::Correct;
+            }
+            session.last_active_block = exec::block_height();
+            let refund = match state.config.hint_cost {
+                HintCost::Attempt => {
+                    session.check_count = session.check_count.saturating_add(1);
+                    msg::value()
+                },
+                HintCost::Fee(fee) => msg::value().saturating_sub(fee),
+                HintCost::ScorePenalty(_) => msg::value(),
+            };
+            log_event(&mut state.next_event_seq, state.config.indexer, key, SessionLogEvent::Hint { position, letter: letter.clone() });
+            msg::reply(SessionEvent::HintRevealed { game_id, position, letter }, refund)
+                .expect("request_hint: error in replying `HintRevealed`");
+        },
+        SessionEvent::Error(error) => {
+            session.advance(SessionInput::Resumed).expect("request_hint: wrong status");
+            reply_hint_error(error);
+        },
+        event @ SessionEvent::GameOver { .. } => {
+            // ended by a timeout or `EmergencyStop` while waiting for wordle
+            session.advance(SessionInput::Settled(session.result.clone())).expect("request_hint: wrong status");
+            msg::reply(event, msg::value()).expect("request_hint: error in replying `GameOver`");
+        },
+        _ => panic!("request_hint: invalid received event"),
     }
 }
 
+/// Replies `error` to `RequestHint`, the fee is only kept for a revealed letter.
+fn reply_hint_error(error: SessionError) {
+    msg::reply(SessionEvent::Error(error), msg::value()).expect("request_hint: error in replying `SessionEvent::Error`");
+}
+
+/// Settles a game whose result has just been set: scores it and records it in the stats,
+/// ends the game in the Wordle program, which already dropped won games, reports challenge
+/// results and logs `event`.
+fn finish_game(state: &mut GameSessionState, key: SessionKey, event: SessionLogEvent) {
+    let Some(session) = state.user_to_session.get_mut(&key) else {
+        return;
+    };
+    let (user, game_id) = key;
+    release_timeout_reservation(&mut state.gas_metrics, session);
+    log_event(&mut state.next_event_seq, state.config.indexer, key, event);
+    if session.result != SessionResult::Void && !session.mode.is_practice() {
+        // the challenger picked the word, so a challenge game counts towards the player's totals
+        // but is neither scored nor ranked
+        let ranked = session.challenge.is_none();
+        if ranked {
+            let won = session.result == SessionResult::Win;
+            let word_wins = match session.answer.clone().filter(|_| won) {
+                Some(answer) => {
+                    let wins = state.word_wins.entry((session.bank.clone(), answer)).or_default();
+                    *wins += 1;
+                    *wins - 1
+                },
+                None => 0,
+            };
+            let hint_penalty = match state.config.hint_cost {
+                HintCost::ScorePenalty(points) => points.saturating_mul(session.hints.len() as u32),
+                HintCost::Attempt | HintCost::Fee(_) => 0,
+            };
+            let input = ScoreInput {
+                won,
+                attempts_left: MAX_ATTEMPTS.saturating_sub(session.check_count),
+                blocks: session.last_active_block.saturating_sub(session.start_block),
+                hint_penalty,
+                word_wins,
+            };
+            session.score = Some(Score { version: state.scoring_version, points: state.config.scoring.points(&input) });
+        }
+
+        let stats = state.user_stats.entry(user).or_default();
+        stats.record(ranked.then_some(session.bank.as_str()), &session.result, session.last_active_block);
+        stats.hints_used += session.hints.len() as u32;
+        if let Some(score) = session.score {
+            stats.record_score(&session.bank, &score);
+            if session.result == SessionResult::Win {
+                stats.record_solve(session.last_active_block - session.start_block, &state.config.anti_abuse);
+            }
+        }
+    }
+    if session.result != SessionResult::Win {
+        end_wordle_game(session.backend, user, game_id);
+    }
+    let Some(challenge_id) = session.challenge else {
+        return;
+    };
+    if let Some(challenge) = state.challenges.get_mut(&challenge_id) {
+        challenge.status = ChallengeStatus::Finished(session.result.clone());
+        let event = SessionEvent::ChallengeFinished { challenge_id, result: session.result.clone() };
+        for player in [challenge.challenger, challenge.friend] {
+            msg::send(player, event.clone(), 0)
+                .expect("finish_game: error in sending `ChallengeFinished`");
+        }
+    }
+}
+
+/// Ends the practice games of `user` that are waiting for a guess, so a new one can take their place.
+fn end_practice_games(state: &mut GameSessionState, user: ActorId) {
+    let keys: Vec<SessionKey> = state.user_to_session
+        .user_sessions(user)
+        .filter(|(_, session)| session.mode.is_practice() && session.is_open())
+        .map(|(key, _)| *key)
+        .collect();
+    for key in keys {
+        let session = state.user_to_session.get_mut(&key).unwrap();
+        if session.advance(SessionInput::Finished(SessionResult::Forfeited)).is_err() {
+            continue;
+        }
+        session.last_active_block = exec::block_height();
+        finish_game(state, key, SessionLogEvent::Forfeit);
+        msg::send(user, SessionEvent::GameOver { game_id: key.1, result: SessionResult::Forfeited }, 0)
+            .expect("end_practice_games: error in sending `GameOver(Forfeited)`");
+    }
+}
+
+/// Sends the next entry of the event log to the indexer.
+fn log_event(next_seq: &mut u64, indexer: ActorId, (user, game_id): SessionKey, event: SessionLogEvent) {
+    let entry = SessionLogEntry { seq: *next_seq, block: exec::block_height(), user, game_id, event };
+    *next_seq += 1;
+    msg::send(indexer, entry, 0).expect("log_event: error in sending `SessionLogEntry`");
+}
+
+fn end_wordle_game(wordle_program: ActorId, user: ActorId, game_id: GameId) {
+    msg::send(wordle_program, Action::EndGame { user, game_id }, 0)
+        .expect("end_wordle_game: error in sending `Action::EndGame`");
+}
+
+fn schedule_cleanup(delay: u32) {
+    msg::send_delayed(exec::program_id(), SessionAction::Cleanup, 0, delay)
+        .expect("schedule_cleanup: error in sending `SessionAction::Cleanup`");
+}
+
+fn cleanup() {
+    if msg::source() != exec::program_id() {
+        panic!("cleanup: only the program itself can run cleanup");
+    }
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("cleanup: GAME_SESSION_STATE is not initialized")};
+
+    if state.importing {
+        schedule_cleanup(state.config.cleanup_interval);
+        return;
+    }
+
+    let block = exec::block_height();
+    let expired = state.expired_sessions(block);
+    debug!("cleanup: compacting {} sessions at block {}", expired.len(), block);
+    for key in expired {
+        // results are already folded into `user_stats` when a game ends
+        state.user_to_session.remove(&key);
+        state.user_stats.entry(key.0).or_default();
+    }
+
+    schedule_cleanup(state.config.cleanup_interval);
+}
+
+fn reply_error(error: SessionError) {
+    debug!("reply error: {:?}", error);
+    msg::reply(SessionEvent::Error(error), 0).expect("Error in replying `SessionEvent::Error`");
+}
+
+fn check_owner(state: &GameSessionState) -> Result<(), SessionError> {
+    if msg::source() == state.owner {
+        Ok(())
+    } else {
+        Err(SessionError::NotOwner)
+    }
+}
+
+fn export_state(cursor: u32, limit: u32) {
+    let state = unsafe {GAME_SESSION_STATE.as_ref()
+        .expect("export_state: GAME_SESSION_STATE is not initialized")};
+    if let Err(error) = check_owner(state) {
+        reply_error(error);
+        return;
+    }
+
+    // sessions come first in store order, so importing the pages one after the other rebuilds
+    // the store as it was, then the stats of every user
+    let limit = limit.clamp(1, MAX_EXPORT_PAGE) as usize;
+    let sessions = state.user_to_session.len();
+    let entries = sessions + state.user_stats.len();
+    let cursor = cursor as usize;
+    let chunk = StateChunkV3 {
+        user_sessions: state.user_to_session
+            .iter()
+            .skip(cursor)
+            .take(limit)
+            // a reservation belongs to this program, the importing one takes its own
+            .map(|((user, _), session)| (*user, Session { timeout_reservation: None, ..session.clone() }))
+            .collect(),
+        user_stats: state.user_stats
+            .iter()
+            .skip(cursor.saturating_sub(sessions))
+            .take((cursor + limit).saturating_sub(sessions.max(cursor)))
+            .map(|(user, stats)| (*user, stats.clone()))
+            .collect(),
+    };
+    let next = cursor + limit;
+    let next_cursor = if next < entries { Some(next as u32) } else { None };
+
+    msg::reply(SessionEvent::StateExported { chunk: StateChunk::V3(chunk), next_cursor }, 0)
+        .expect("export_state: error in replying `StateExported`");
+}
+
+fn import_state(chunk: StateChunk) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("import_state: GAME_SESSION_STATE is not initialized")};
+    if let Err(error) = check_owner(state) {
+        reply_error(error);
+        return;
+    }
+    if !state.importing {
+        reply_error(SessionError::NotImporting);
+        return;
+    }
+
+    let version = chunk.version();
+    if let Err(error) = import_chunk(state, chunk) {
+        reply_error(error);
+        return;
+    }
+    msg::reply(SessionEvent::StateImported { version }, 0)
+        .expect("import_state: error in replying `StateImported`");
+}
+
+/// Imports `chunk`, or nothing at all if the open games in it can't all reserve gas for
+/// their timeouts.
+fn import_chunk(state: &mut GameSessionState, chunk: StateChunk) -> Result<(), SessionError> {
+    debug!("import_chunk: migrating chunk from v{} to v{}", chunk.version(), STATE_VERSION);
+    let StateChunkV3 { user_sessions, user_stats } = chunk.migrate();
+    let mut sessions = Vec::with_capacity(user_sessions.len());
+    for (user, mut session) in user_sessions {
+        // a request in flight belonged to the old program and will never be answered
+        let interrupted = session.result == SessionResult::Ongoing && session.status != SessionStatus::CheckWordWaiting;
+        if interrupted {
+            session.status = SessionStatus::StartGameWaiting;
+            session.result = SessionResult::Void;
+        }
+        session.msg_ids = (MessageId::zero(), MessageId::zero());
+        session.timeout_reservation = None;
+        // sessions from before backends were tracked stay on the first one
+        if session.backend == ActorId::zero() {
+            session.backend = state.backends[0].program;
+        }
+        sessions.push((user, session, interrupted));
+    }
+
+    // the timeouts the old program scheduled run there, not here
+    let mut reservations = Vec::new();
+    for _ in sessions.iter().filter(|(_, session, _)| session.is_open()) {
+        let Ok(reservation_id) = ReservationId::reserve(TIMEOUT_RESERVATION_GAS, TIMEOUT_RESERVATION_BLOCKS) else {
+            for reservation_id in reservations {
+                if let Err(error) = reservation_id.unreserve() {
+                    debug!("import_chunk: failed to unreserve: {:?}", error);
+                }
+            }
+            return Err(SessionError::NotEnoughGasForTimeout);
+        };
+        reservations.push(reservation_id);
+    }
+
+    let mut reservations = reservations.into_iter();
+    // inserted in chunk order, which is the insertion order of the exporting program
+    for (user, mut session, interrupted) in sessions {
+        let key = (user, session.game_id);
+        if session.is_open() {
+            session.timeout_reservation = reservations.next();
+            state.gas_metrics.reserved_gas += TIMEOUT_RESERVATION_GAS;
+            state.gas_metrics.active_reservations += 1;
+            let delay = (session.start_block + CHECK_GAME_STATUS_DELAY).saturating_sub(exec::block_height());
+            msg::send_delayed(exec::program_id(), SessionAction::CheckGameStatus { user, game_id: key.1 }, 0, delay)
+                .expect("import_chunk: error in sending `SessionAction::CheckGameStatus`");
+        }
+        state.next_game_id = state.next_game_id.max(session.game_id + 1);
+        state.user_to_session.insert(key, session);
+        if interrupted {
+            finish_game(state, key, SessionLogEvent::Void);
+        }
+    }
+    for (user, stats) in user_stats {
+        state.user_stats.insert(user, stats);
+    }
+    Ok(())
+}
+
+fn transfer_ownership(new_owner: ActorId) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("transfer_ownership: GAME_SESSION_STATE is not initialized")};
+    if let Err(error) = check_owner(state) {
+        reply_error(error);
+        return;
+    }
+
+    state.owner = new_owner;
+    msg::reply(SessionEvent::OwnershipTransferred { new_owner }, 0)
+        .expect("transfer_ownership: error in replying `OwnershipTransferred`");
+}
+
+fn set_paused(paused: bool) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("set_paused: GAME_SESSION_STATE is not initialized")};
+    if let Err(error) = check_owner(state) {
+        reply_error(error);
+        return;
+    }
+
+    state.paused = paused;
+    let event = if paused { SessionEvent::Paused } else { SessionEvent::Unpaused };
+    msg::reply(event, 0).expect("set_paused: error in replying `Paused`/`Unpaused`");
+}
+
+fn emergency_stop() {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("emergency_stop: GAME_SESSION_STATE is not initialized")};
+    // the program sends itself the games left over by the previous run
+    let continued = msg::source() == exec::program_id();
+    if !continued {
+        if let Err(error) = check_owner(state) {
+            reply_error(error);
+            return;
+        }
+    }
+
+    state.paused = true;
+    let open: Vec<SessionKey> = state.user_to_session
+        .iter()
+        .filter(|(_, session)| session.is_open())
+        .map(|(key, _)| *key)
+        .take(EMERGENCY_STOP_BATCH + 1)
+        .collect();
+    let voided = &open[..open.len().min(EMERGENCY_STOP_BATCH)];
+    for key in voided {
+        let session = state.user_to_session.get_mut(key).unwrap();
+        session.last_active_block = exec::block_height();
+        if session.advance(SessionInput::Finished(SessionResult::Void)).is_ok() {
+            msg::send(key.0, SessionEvent::GameOver { game_id: key.1, result: SessionResult::Void }, 0)
+                .expect("emergency_stop: error in sending `GameOver(Void)`");
+        } else {
+            // the waiting message replies `GameOver(Void)` itself once woken, an already
+            // woken one picks up the new status when it runs
+            let waiting = session.status.is_waiting();
+            session.advance(SessionInput::Interrupted(SessionResult::Void)).expect("emergency_stop: wrong status");
+            if waiting {
+                exec::wake(session.msg_ids.1).expect("Failed to wake message");
+            }
+        }
+        session.msg_ids.0 = MessageId::zero();
+    }
+    for key in voided {
+        finish_game(state, *key, SessionLogEvent::Void);
+    }
+    if open.len() > voided.len() {
+        msg::send(exec::program_id(), SessionAction::EmergencyStop, 0)
+            .expect("emergency_stop: error in sending `SessionAction::EmergencyStop`");
+    }
+
+    if !continued {
+        msg::reply(SessionEvent::EmergencyStopped { voided: voided.len() as u32 }, 0)
+            .expect("emergency_stop: error in replying `EmergencyStopped`");
+    }
+}
+
+fn set_indexer(indexer: ActorId) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("set_indexer: GAME_SESSION_STATE is not initialized")};
+    if let Err(error) = check_owner(state) {
+        reply_error(error);
+        return;
+    }
+
+    state.config.indexer = indexer;
+    msg::reply(SessionEvent::IndexerSet { indexer }, 0).expect("set_indexer: error in replying `IndexerSet`");
+}
+
+fn set_anti_abuse(config: AntiAbuseConfig) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("set_anti_abuse: GAME_SESSION_STATE is not initialized")};
+    if let Err(error) = check_owner(state) {
+        reply_error(error);
+        return;
+    }
+
+    state.config.anti_abuse = config;
+    msg::reply(SessionEvent::AntiAbuseSet { config }, 0).expect("set_anti_abuse: error in replying `AntiAbuseSet`");
+}
+
+fn set_hint_cost(cost: HintCost) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("set_hint_cost: GAME_SESSION_STATE is not initialized")};
+    if let Err(error) = check_owner(state) {
+        reply_error(error);
+        return;
+    }
+
+    state.config.hint_cost = cost;
+    msg::reply(SessionEvent::HintCostSet { cost }, 0).expect("set_hint_cost: error in replying `HintCostSet`");
+}
+
+fn set_scoring(scoring: ScoringConfig) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("set_scoring: GAME_SESSION_STATE is not initialized")};
+    if let Err(error) = check_owner(state) {
+        reply_error(error);
+        return;
+    }
+
+    // scores of the old formula stay as they are, they are just no longer counted
+    state.config.scoring = scoring;
+    state.scoring_version += 1;
+    msg::reply(SessionEvent::ScoringSet { version: state.scoring_version, scoring }, 0)
+        .expect("set_scoring: error in replying `ScoringSet`");
+}
+
+fn set_flagged(user: ActorId, flagged: bool) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("set_flagged: GAME_SESSION_STATE is not initialized")};
+    if let Err(error) = check_moderator(state) {
+        reply_error(error);
+        return;
+    }
+
+    let stats = state.user_stats.entry(user).or_default();
+    stats.flagged = flagged;
+    if !flagged {
+        // cleared users start counting fast solves again
+        stats.fast_solves = 0;
+    }
+    msg::reply(SessionEvent::FlaggedSet { user, flagged }, 0).expect("set_flagged: error in replying `FlaggedSet`");
+}
+
+fn add_backend(program: ActorId) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("add_backend: GAME_SESSION_STATE is not initialized")};
+    if let Err(error) = check_owner(state) {
+        reply_error(error);
+        return;
+    }
+    // an `AddBackend` still waiting for the handshake counts as well
+    if state.backends.iter().any(|backend| backend.program == program) || handshake_pending(program) {
+        reply_error(SessionError::BackendExists);
+        return;
+    }
+
+    let info = match handshake(program) {
+        Ok(info) => info,
+        Err(reason) => {
+            debug!("add_backend: {:x?} refused: {}", program, reason);
+            reply_error(SessionError::IncompatibleBackend);
+            return;
+        },
+    };
+    state.backends.push(Backend::new(program, info));
+    msg::reply(SessionEvent::BackendAdded { program }, 0).expect("add_backend: error in replying `BackendAdded`");
+}
+
+fn remove_backend(program: ActorId) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("remove_backend: GAME_SESSION_STATE is not initialized")};
+    if let Err(error) = check_owner(state) {
+        reply_error(error);
+        return;
+    }
+    let Some(index) = state.backends.iter().position(|backend| backend.program == program) else {
+        reply_error(SessionError::UnknownBackend);
+        return;
+    };
+
+    state.backends.remove(index);
+    if state.next_backend as usize >= state.backends.len() {
+        state.next_backend = 0;
+    }
+    msg::reply(SessionEvent::BackendRemoved { program }, 0).expect("remove_backend: error in replying `BackendRemoved`");
+}
+
+fn check_moderator(state: &GameSessionState) -> Result<(), SessionError> {
+    let source = msg::source();
+    if source == state.owner || state.moderators.contains(&source) {
+        Ok(())
+    } else {
+        Err(SessionError::NotModerator)
+    }
+}
+
+fn set_moderator(moderator: ActorId, add: bool) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("set_moderator: GAME_SESSION_STATE is not initialized")};
+    if let Err(error) = check_owner(state) {
+        reply_error(error);
+        return;
+    }
+
+    let event = if add {
+        state.moderators.insert(moderator);
+        SessionEvent::ModeratorAdded { moderator }
+    } else {
+        state.moderators.remove(&moderator);
+        SessionEvent::ModeratorRemoved { moderator }
+    };
+    msg::reply(event, 0).expect("set_moderator: error in replying `ModeratorAdded`/`ModeratorRemoved`");
+}
+
+fn submit_word_pack(name: String, alphabet: Alphabet, words: Vec<String>) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("submit_word_pack: GAME_SESSION_STATE is not initialized")};
+    if state.word_packs.contains_key(&name) {
+        reply_error(SessionError::WordPackExists);
+        return;
+    }
+    if words.is_empty() || words.len() > MAX_WORD_PACK_SIZE {
+        reply_error(SessionError::InvalidWordPack);
+        return;
+    }
+    let Ok(mut words) = words.iter().map(|word| alphabet.normalize(word)).collect::<Result<Vec<String>, _>>() else {
+        reply_error(SessionError::InvalidWordPack);
+        return;
+    };
+    words.sort();
+    words.dedup();
+
+    state.word_packs.insert(name.clone(), WordPack {
+        creator: msg::source(),
+        alphabet,
+        words,
+        status: WordPackStatus::Pending,
+        play_count: 0,
+        registration: None,
+    });
+    msg::reply(SessionEvent::WordPackSubmitted { name }, 0)
+        .expect("submit_word_pack: error in replying `WordPackSubmitted`");
+}
+
+fn review_word_pack(name: String, approve: bool) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("review_word_pack: GAME_SESSION_STATE is not initialized")};
+    if let Err(error) = check_moderator(state) {
+        reply_error(error);
+        return;
+    }
+    let Some(pack) = state.word_packs.get_mut(&name) else {
+        reply_error(SessionError::UnknownWordPack);
+        return;
+    };
+    if pack.status != WordPackStatus::Pending || pack.registration.is_some() {
+        reply_error(SessionError::WordPackAlreadyReviewed);
+        return;
+    }
+
+    let registrars: Vec<ActorId> = state.backends
+        .iter()
+        .filter(|backend| backend.info.features.contains(&Feature::AddBank))
+        .map(|backend| backend.program)
+        .collect();
+    if approve && registrars.is_empty() {
+        reply_error(SessionError::NotSupported);
+        return;
+    }
+
+    if approve {
+        // every backend gets the bank, the pack is approved once the first one has registered it
+        let action = Action::AddBank { name: name.clone(), alphabet: pack.alphabet, words: pack.words.clone() };
+        for program in registrars {
+            let msg_id = msg::send(program, action.clone(), 0)
+                .expect("review_word_pack: error in sending `Action::AddBank`");
+            pack.registration.get_or_insert(msg_id);
+        }
+    } else {
+        pack.status = WordPackStatus::Rejected;
+    }
+    msg::reply(SessionEvent::WordPackReviewed { name, approve }, 0)
+        .expect("review_word_pack: error in replying `WordPackReviewed`");
+}
+
+fn challenge_friend(friend: ActorId, word: String, bank: Option<String>) {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("challenge_friend: GAME_SESSION_STATE is not initialized")};
+
+    // woken by `handle_reply` once the Wordle program has checked the word, or by the timeout
+    let validated = state.challenges.iter()
+        .find(|(_, challenge)| challenge.msg_ids.1 == msg::id())
+        .map(|(challenge_id, challenge)| (*challenge_id, challenge.status.clone()));
+    if let Some((challenge_id, status)) = validated {
+        let challenge = state.challenges.get_mut(&challenge_id).unwrap();
+        challenge.msg_ids = (MessageId::zero(), MessageId::zero());
+        match status {
+            ChallengeStatus::Pending => {
+                let word_hash = challenge.word_hash;
+                msg::send(friend, SessionEvent::ChallengeReceived { challenge_id, challenger: challenge.challenger, word_hash }, 0)
+                    .expect("challenge_friend: error in sending `ChallengeReceived`");
+                msg::reply(SessionEvent::ChallengeCreated { challenge_id, word_hash }, 0)
+                    .expect("challenge_friend: error in replying `ChallengeCreated`");
+            },
+            ChallengeStatus::Invalid(error) => {
+                state.challenges.remove(&challenge_id);
+                reply_error(SessionError::Wordle(error));
+            },
+            ChallengeStatus::Validating => {
+                state.challenges.remove(&challenge_id);
+                reply_error(SessionError::BackendUnavailable);
+            },
+            _ => panic!("challenge_friend: wrong challenge status"),
+        }
+        return;
+    }
+
+    if state.importing {
+        reply_error(SessionError::ImportInProgress);
+        return;
+    }
+    if state.paused {
+        reply_error(SessionError::Paused);
+        return;
+    }
+    if friend == msg::source() {
+        reply_error(SessionError::InvalidChallenge);
+        return;
+    }
+    let block = exec::block_height();
+    let validators: Vec<&Backend> = state.backends
+        .iter()
+        .filter(|backend| backend.info.features.contains(&Feature::ValidateWord))
+        .collect();
+    if validators.is_empty() {
+        reply_error(SessionError::NotSupported);
+        return;
+    }
+    let Some(validator) = validators.iter().find(|backend| backend.is_healthy(block)).map(|backend| backend.program) else {
+        reply_error(SessionError::BackendUnavailable);
+        return;
+    };
+
+    let challenge_id = state.next_challenge_id;
+    state.next_challenge_id += 1;
+    let mut random_input: [u8; 32] = exec::program_id().into();
+    random_input[..8].copy_from_slice(&challenge_id.to_le_bytes());
+    let (salt, _) = exec::random(random_input).expect("challenge_friend: error in getting random salt");
+
+    let bank = bank.unwrap_or_else(|| DEFAULT_BANK.to_string());
+    let msg_id = msg::send(validator, Action::ValidateWord { bank: bank.clone(), word }, 0)
+        .expect("challenge_friend: error in sending `Action::ValidateWord`");
+    // the word and its hash are filled in once the Wordle program has normalized it
+    state.challenges.insert(challenge_id, Challenge {
+        challenger: msg::source(),
+        friend,
+        bank,
+        word: String::new(),
+        salt,
+        word_hash: [0; 32],
+        status: ChallengeStatus::Validating,
+        msg_ids: (msg_id, msg::id()),
+    });
+
+    debug!("challenge_friend: `ValidateWord` wait");
+    exec::wait_for(BACKEND_REPLY_TIMEOUT);
+}
+
+fn finish_import() {
+    let state = unsafe {GAME_SESSION_STATE.as_mut()
+        .expect("finish_import: GAME_SESSION_STATE is not initialized")};
+    if let Err(error) = check_owner(state) {
+        reply_error(error);
+        return;
+    }
+    if !state.importing {
+        reply_error(SessionError::NotImporting);
+        return;
+    }
+
+    state.importing = false;
+    msg::reply(SessionEvent::ImportFinished, 0).expect("finish_import: error in replying `ImportFinished`");
+}
+
 #[no_mangle]
 extern "C" fn handle() {
     debug!("---handle---");
     let action: SessionAction = msg::load().expect("Unable to decode handle");
     debug!("action is {:x?}", &action);
+    // `state()` runs without the program balance, so it is taken here
+    if let Some(state) = unsafe { GAME_SESSION_STATE.as_mut() } {
+        state.gas_metrics.balance = exec::value_available();
+    }
 
     match &action {
-        SessionAction::StartGame => start_game(),
-        SessionAction::CheckWord { word } => check_word(word.to_string()),
-        SessionAction::CheckGameStatus { user } => check_game_status(&user),
+        SessionAction::StartGame { bank } => start_game(bank.clone(), None, GameMode::Ranked),
+        SessionAction::StartPractice { bank, unlimited_guesses } => {
+            start_game(bank.clone(), None, GameMode::Practice { unlimited_guesses: *unlimited_guesses })
+        },
+        SessionAction::CheckWord { game_id, word } => check_word(*game_id, word.to_string()),
+        SessionAction::Forfeit { game_id } => forfeit(*game_id),
+        SessionAction::RequestHint { game_id } => request_hint(*game_id),
+        SessionAction::CheckGameStatus { user, game_id } => check_game_status(*user, *game_id),
+        SessionAction::Cleanup => cleanup(),
+        SessionAction::ExportState { cursor, limit } => export_state(*cursor, *limit),
+        SessionAction::ImportState { chunk } => import_state(chunk.clone()),
+        SessionAction::FinishImport => finish_import(),
+        SessionAction::TransferOwnership { new_owner } => transfer_ownership(*new_owner),
+        SessionAction::Pause => set_paused(true),
+        SessionAction::Unpause => set_paused(false),
+        SessionAction::EmergencyStop => emergency_stop(),
+        SessionAction::SetIndexer { indexer } => set_indexer(*indexer),
+        SessionAction::SetAntiAbuse { config } => set_anti_abuse(*config),
+        SessionAction::SetHintCost { cost } => set_hint_cost(*cost),
+        SessionAction::SetScoring { scoring } => set_scoring(*scoring),
+        SessionAction::SetFlagged { user, flagged } => set_flagged(*user, *flagged),
+        SessionAction::AddBackend { program } => add_backend(*program),
+        SessionAction::RemoveBackend { program } => remove_backend(*program),
+        SessionAction::AddModerator { moderator } => set_moderator(*moderator, true),
+        SessionAction::RemoveModerator { moderator } => set_moderator(*moderator, false),
+        SessionAction::SubmitWordPack { name, alphabet, words } => submit_word_pack(name.clone(), *alphabet, words.clone()),
+        SessionAction::ReviewWordPack { name, approve } => review_word_pack(name.clone(), *approve),
+        SessionAction::ChallengeFriend { friend, word, bank } => challenge_friend(*friend, word.clone(), bank.clone()),
+        SessionAction::AcceptChallenge { challenge_id } => start_game(None, Some(*challenge_id), GameMode::Ranked),
+    }
+}
+
+/// Hands the Wordle program's answer to the message waiting for it.
+fn deliver_reply(session: &mut Session, event: SessionEvent) {
+    match session.advance(SessionInput::Replied(event)) {
+        Ok(()) => exec::wake(session.msg_ids.1).expect("Failed to wake message"),
+        Err(_) => debug!("handle_reply: session {} does not wait for a reply, status is {:x?}", session.game_id, session.status),
     }
 }
 
 #[no_mangle]
 extern "C" fn handle_reply() {
     debug!("---handle_reply---");
-    let state = unsafe {GAME_SESSION_STATE.as_mut().expect("GAME_SESSION_STATE is not initialized")};
     let reply_to = msg::reply_to().expect("Failed to query reply_to data");
-    let reply_message: Event = msg::load().expect("Unable to decode wordle's reply message");
+    // a panicking or out of gas backend replies with an error code instead of an `Event`
+    let reply_message: Option<Event> = match msg::reply_code() {
+        Ok(ReplyCode::Success(_)) => msg::load().ok(),
+        _ => None,
+    };
     debug!("Reply message: {:x?}", reply_message);
 
+    let handshakes = unsafe { HANDSHAKES.get_or_insert_with(BTreeMap::new) };
+    let handshake = handshakes.iter_mut().find(|(_, handshake)| matches!(handshake, Handshake::Sent { msg_id, .. } if *msg_id == reply_to));
+    if let Some((waiting, handshake)) = handshake {
+        let info = match reply_message {
+            Some(Event::Info(info)) => Some(info),
+            _ => None,
+        };
+        *handshake = Handshake::Received(info);
+        exec::wake(*waiting).expect("Failed to wake message");
+        return;
+    }
+
+    let state = unsafe {GAME_SESSION_STATE.as_mut().expect("GAME_SESSION_STATE is not initialized")};
+    let failure = match &reply_message {
+        None => Some(SessionError::BackendUnavailable),
+        Some(Event::Error(error)) => Some(SessionError::Wordle(error.clone())).filter(SessionError::is_backend_failure),
+        Some(_) => None,
+    };
+    if let Some(backend) = state.backend_mut(msg::source()) {
+        if failure.is_some() {
+            backend.record_failure(exec::block_height());
+        } else {
+            backend.record_success();
+        }
+    }
+    let Some(reply_message) = reply_message else {
+        if let Some(session) = state.user_to_session.values_mut().find(|session| session.msg_ids.0 == reply_to) {
+            deliver_reply(session, SessionEvent::Error(SessionError::BackendUnavailable));
+        } else if let Some(challenge) = state.challenges.values_mut().find(|challenge| challenge.msg_ids.0 == reply_to) {
+            // still validating, so `challenge_friend` gives up on it
+            exec::wake(challenge.msg_ids.1).expect("Failed to wake message");
+        } else {
+            debug!("handle_reply: error reply for an unknown message");
+        }
+        return;
+    };
+
     match &reply_message {
-        Event::GameStarted { user } => {
-            if let Some(session) = state.user_to_session.get_mut(user) {
+        Event::GameStarted { user, game_id, alphabet } => {
+            if let Some(session) = state.user_to_session.get_mut(&(*user, *game_id)) {
                 if reply_to == session.msg_ids.0 {
-                    session.status = SessionStatus::ReplyReceived(SessionEvent::GameStarted);
-                    exec::wake(session.msg_ids.1).expect("Failed to wake message");
+                    deliver_reply(session, SessionEvent::GameStarted { game_id: *game_id, alphabet: *alphabet });
                 } else {
-                    panic!("handle_reply: reply_to does not match the message id");
+                    // a late reply from a backend the game has failed over from
+                    debug!("handle_reply: stale `GameStarted` from {:x?}", msg::source());
+                    end_wordle_game(msg::source(), *user, *game_id);
                 }
             } else {
-                panic!("handle_reply: GameStarted, non existing user");
+                // the start already gave up on this backend, or the session is gone
+                debug!("handle_reply: `GameStarted` for a missing session from {:x?}", msg::source());
+                end_wordle_game(msg::source(), *user, *game_id);
             }
         },
-        Event::WordChecked { user, correct_positions, contained_in_word } => {
-            if let Some(session) = state.user_to_session.get_mut(user) {
+        Event::WordChecked { user, game_id, correct_positions, contained_in_word } => {
+            if let Some(session) = state.user_to_session.get_mut(&(*user, *game_id)) {
                 if reply_to == session.msg_ids.0 {
+                    // the keyboard is folded in by `check_word`, which knows the guessed word
                     let event = SessionEvent::WordChecked {
-                        correct_positions: correct_positions.clone(), 
+                        game_id: *game_id,
+                        correct_positions: correct_positions.clone(),
                         contained_in_word: contained_in_word.clone(),
+                        keyboard: Vec::new(),
                     };
-                    session.status = SessionStatus::ReplyReceived(event);
-                    exec::wake(session.msg_ids.1).expect("Failed to wake message");
+                    deliver_reply(session, event);
                 } else {
-                    panic!("handle_reply: reply_to does not match the message id");
+                    // the guess timed out and was not counted
+                    debug!("handle_reply: stale `WordChecked` from {:x?}", msg::source());
                 }
             } else {
-                panic!("handle_reply: WordChecked, non existing user");
+                debug!("handle_reply: `WordChecked` for a missing session from {:x?}", msg::source());
+            }
+        },
+        Event::LetterRevealed { user, game_id, position, letter } => {
+            match state.user_to_session.get_mut(&(*user, *game_id)).filter(|session| session.msg_ids.0 == reply_to) {
+                Some(session) => {
+                    deliver_reply(session, SessionEvent::HintRevealed { game_id: *game_id, position: *position, letter: letter.clone() });
+                },
+                None => debug!("handle_reply: stale `LetterRevealed` from {:x?}", msg::source()),
+            }
+        },
+        Event::GameEnded { user, game_id } => {
+            debug!("handle_reply: wordle game {} of {:x?} ended", game_id, user);
+        },
+        Event::WordValidated { word, .. } => {
+            let Some(challenge) = state.challenges.values_mut().find(|challenge| challenge.msg_ids.0 == reply_to) else {
+                debug!("handle_reply: WordValidated for a challenge that timed out");
+                return;
+            };
+            challenge.word_hash = challenge_commitment(&challenge.salt, word);
+            challenge.word = word.clone();
+            challenge.status = ChallengeStatus::Pending;
+            exec::wake(challenge.msg_ids.1).expect("Failed to wake message");
+        },
+        Event::Error(error) => {
+            // wordle errors carry no user, so find the session by the message it replies to
+            let session = state.user_to_session
+                .values_mut()
+                .find(|session| session.msg_ids.0 == reply_to);
+            if let Some(session) = session {
+                deliver_reply(session, SessionEvent::Error(SessionError::Wordle(error.clone())));
+            } else if let Some(challenge) = state.challenges.values_mut().find(|challenge| challenge.msg_ids.0 == reply_to) {
+                challenge.status = ChallengeStatus::Invalid(error.clone());
+                exec::wake(challenge.msg_ids.1).expect("Failed to wake message");
+            } else if let Some(pack) = state.word_packs.values_mut().find(|pack| pack.registration == Some(reply_to)) {
+                // the pack stays pending, a moderator can approve it again or reject it
+                debug!("handle_reply: word pack registration failed: {:?}", error);
+                pack.registration = None;
+            } else {
+                debug!("handle_reply: wordle error {:?} for an unknown message", error);
+            }
+        },
+        Event::BankAdded { name } => {
+            if let Some(pack) = state.word_packs.get_mut(name) {
+                pack.registration = None;
+                pack.status = WordPackStatus::Approved;
+            }
+            if let Some(backend) = state.backend_mut(msg::source()) {
+                backend.info.banks.push(name.clone());
             }
         },
+        Event::SessionProgramAdded { .. }
+        | Event::SessionProgramRemoved { .. }
+        | Event::GamesExported { .. }
+        | Event::GamesImported { .. }
+        | Event::OwnershipTransferred { .. }
+        | Event::Paused
+        | Event::Unpaused
+        | Event::Info(_) => {
+            debug!("handle_reply: unexpected wordle event");
+        },
     }
 }
 
 #[no_mangle]
 extern "C" fn state() {
-    let game_session = unsafe { GAME_SESSION_STATE.take().expect("Unexpected error in taking state") };
-    msg::reply::<State>(game_session.into(), 0)
-        .expect("Failed to encode or reply with `GameSessionState` from `state()`");
+    // readers predating `StateQuery` send no payload and get the whole state
+    if msg::size() == 0 {
+        let game_session = unsafe { GAME_SESSION_STATE.take().expect("Unexpected error in taking state") };
+        msg::reply::<State>(game_session.into(), 0)
+            .expect("Failed to encode or reply with `GameSessionState` from `state()`");
+        return;
+    }
+
+    let game_session = unsafe { GAME_SESSION_STATE.as_ref().expect("GAME_SESSION_STATE is not initialized") };
+    let query: StateQuery = msg::load().expect("Unable to decode `StateQuery`");
+    let reply = match query {
+        StateQuery::WordleProgram => StateReply::WordleProgram(game_session.backends.first().map_or(ActorId::zero(), |backend| backend.program)),
+        StateQuery::Backends => StateReply::Backends(game_session.backends.clone()),
+        StateQuery::UserSession { user, game_id } => StateReply::UserSession(game_session.user_to_session.get(&(user, game_id)).cloned()),
+        StateQuery::OpenGames { user } => StateReply::OpenGames(game_session.open_games(user)),
+        StateQuery::Sessions { filter, cursor, limit } => {
+            let (sessions, next_cursor) = game_session.sessions_page(&filter, cursor, limit);
+            StateReply::Sessions { sessions, next_cursor }
+        },
+    };
+    msg::reply(reply, 0).expect("Failed to encode or reply with `StateReply` from `state()`");
 }
\ No newline at end of file
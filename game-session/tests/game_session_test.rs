@@ -1,13 +1,22 @@
-use gstd::{prelude::*, ActorId};
+use gstd::{prelude::*, collections::BTreeMap, ActorId, MessageId};
 use gtest::{Log, Program, System};
 use game_session_io::*;
+use wordle_io::{Action, Alphabet, Event, Feature, GameId, WordleError, WordleInfo, WordleInit, WordleState, PROTOCOL_VERSION};
 
 const WORDLE_ID: u64 = 1;
 const GAME_SESSION_ID: u64 = 2;
+const WORDLE2_ID: u64 = 3;
 const USER1: u64 = 10;
 const USER2: u64 = 11;
+const USER3: u64 = 12;
+/// `wordle_engine::MAX_ATTEMPTS`
+const MAX_ATTEMPTS: u8 = 6;
 
 fn setup() -> System {
+    setup_with_config(SessionConfig::default())
+}
+
+fn setup_with_config(config: SessionConfig) -> System {
     let sys = System::new();
     sys.init_logger();
 
@@ -16,8 +25,9 @@ fn setup() -> System {
 
     // let user_id: ActorId = USER1.into();
     let wordle_id: ActorId = WORDLE_ID.into();
-    assert!(!wordle.send(USER1, wordle_id).main_failed());
-    assert!(!game_session.send(USER1, wordle_id).main_failed());
+    let game_session_id: ActorId = GAME_SESSION_ID.into();
+    assert!(!wordle.send(USER1, WordleInit { session_programs: vec![game_session_id] }).main_failed());
+    assert!(!game_session.send(USER1, GameSessionInit { wordle_program: wordle_id, config, import: None }).main_failed());
     return sys;
 }
 
@@ -27,9 +37,9 @@ fn test_win() {
     let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
 
     // user1 starts game, and check words (the hidden word is "house" in test mode)
-    assert!(!game_session.send(USER1, SessionAction::StartGame).main_failed());
-    assert!(!game_session.send(USER1, SessionAction::CheckWord { word: "human".to_string() }).main_failed());
-    assert!(!game_session.send(USER1, SessionAction::CheckWord { word: "house".to_string() }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "house".to_string() }).main_failed());
 
     let state: State = game_session.read_state(b"").unwrap();
     assert_eq!(state.user_sessions.len(), 1);
@@ -40,23 +50,20 @@ fn test_win() {
 
     // support multiple users:
     // user2 starts game, and check words (the hidden word is "house" in test mode)
-    let user2: ActorId = USER2.into();
-    assert!(!game_session.send(USER2, SessionAction::StartGame).main_failed());
-    assert!(!game_session.send(USER2, SessionAction::CheckWord { word: "human".to_string() }).main_failed());
-    assert!(!game_session.send(USER2, SessionAction::CheckWord { word: "human".to_string() }).main_failed());
-    assert!(!game_session.send(USER2, SessionAction::CheckWord { word: "human".to_string() }).main_failed());
-    assert!(!game_session.send(USER2, SessionAction::CheckWord { word: "human".to_string() }).main_failed());
-    assert!(!game_session.send(USER2, SessionAction::CheckWord { word: "human".to_string() }).main_failed());
-    assert!(!game_session.send(USER2, SessionAction::CheckWord { word: "house".to_string() }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::CheckWord { game_id: 1, word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::CheckWord { game_id: 1, word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::CheckWord { game_id: 1, word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::CheckWord { game_id: 1, word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::CheckWord { game_id: 1, word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::CheckWord { game_id: 1, word: "house".to_string() }).main_failed());
 
     let state: State = game_session.read_state(b"").unwrap();
     assert_eq!(state.user_sessions.len(), 2);
 
-    let session = if user2 == state.user_sessions[0].0 {
-        &state.user_sessions[0].1
-    } else {
-        &state.user_sessions[1].1
-    };
+    // sessions are listed in the order they were started
+    let (user, session) = &state.user_sessions[1];
+    assert_eq!(*user, USER2.into());
     assert_eq!(session.check_count, 6);
     assert_eq!(session.status, SessionStatus::StartGameWaiting);
     assert_eq!(session.result, SessionResult::Lose);
@@ -68,13 +75,13 @@ fn test_lose_with_too_many_check() {
     let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
     
     // user starts game, and check word for >=6 times (the hidden word is "house" in test mode)
-    assert!(!game_session.send(USER1, SessionAction::StartGame).main_failed());
-    assert!(!game_session.send(USER1, SessionAction::CheckWord { word: "human".to_string() }).main_failed());
-    assert!(!game_session.send(USER1, SessionAction::CheckWord { word: "human".to_string() }).main_failed());
-    assert!(!game_session.send(USER1, SessionAction::CheckWord { word: "human".to_string() }).main_failed());
-    assert!(!game_session.send(USER1, SessionAction::CheckWord { word: "human".to_string() }).main_failed());
-    assert!(!game_session.send(USER1, SessionAction::CheckWord { word: "human".to_string() }).main_failed());
-    assert!(!game_session.send(USER1, SessionAction::CheckWord { word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "human".to_string() }).main_failed());
 
     let state: State = game_session.read_state(b"").unwrap();
     assert_eq!(state.user_sessions.len(), 1);
@@ -90,8 +97,8 @@ fn test_lose_with_timeout() {
     let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
     
     // user starts game, and check word for >=6 times (the hidden word is "house" in test mode)
-    assert!(!game_session.send(USER1, SessionAction::StartGame).main_failed());
-    assert!(!game_session.send(USER1, SessionAction::CheckWord { word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "human".to_string() }).main_failed());
 
     sys.spend_blocks(200);
 
@@ -100,12 +107,1260 @@ fn test_lose_with_timeout() {
     assert_eq!(state.user_sessions[0].0, USER1.into());
     assert_eq!(state.user_sessions[0].1.check_count, 1);
     assert_eq!(state.user_sessions[0].1.status, SessionStatus::StartGameWaiting);
-    assert_eq!(state.user_sessions[0].1.result, SessionResult::Lose);
+    assert_eq!(state.user_sessions[0].1.result, SessionResult::TimedOut);
 
     let mailbox = sys.get_mailbox(USER1);
     let log = Log::builder()
         .source(GAME_SESSION_ID)
         .dest(USER1)
-        .payload(SessionEvent::GameOver { result: SessionResult::Lose } );
+        .payload(SessionEvent::GameOver { game_id: 0, result: SessionResult::TimedOut });
     assert!(mailbox.contains(&log));
-}
\ No newline at end of file
+
+    // a timeout is still a loss, told apart from running out of guesses
+    let (_, stats) = &state.user_stats[0];
+    assert_eq!((stats.losses, stats.timeouts, stats.forfeits), (1, 1, 0));
+}
+
+#[test]
+fn test_invalid_cleanup_config() {
+    let sys = setup();
+
+    // cleanup would run every block, or never compact anything
+    for config in [
+        SessionConfig { cleanup_interval: 0, ..SessionConfig::default() },
+        SessionConfig { session_ttl: 0, ..SessionConfig::default() },
+        SessionConfig { cleanup_batch: 0, ..SessionConfig::default() },
+    ] {
+        assert_eq!(config.validate(), Err(SessionError::InvalidConfig));
+        let new_session = Program::from_file(&sys, "../target/wasm32-unknown-unknown/debug/game_session.wasm");
+        let init = GameSessionInit { wordle_program: WORDLE_ID.into(), config, import: None };
+        assert!(new_session.send(USER1, init).main_failed());
+    }
+    assert_eq!(SessionConfig::default().validate(), Ok(()));
+}
+
+#[test]
+fn test_cleanup_compacts_finished_sessions() {
+    let sys = setup_with_config(SessionConfig {
+        session_ttl: 20,
+        cleanup_interval: 10,
+        cleanup_batch: 10,
+        ..SessionConfig::default()
+    });
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    // user1 wins a game, the finished session is kept until it has been idle for `session_ttl` blocks
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "house".to_string() }).main_failed());
+
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.user_sessions.len(), 1);
+
+    sys.spend_blocks(30);
+
+    let state: State = game_session.read_state(b"").unwrap();
+    assert!(state.user_sessions.is_empty());
+    assert_eq!(state.user_stats.len(), 1);
+    assert_eq!(state.user_stats[0].0, USER1.into());
+    assert_eq!(state.user_stats[0].1.games_played, 1);
+    assert_eq!(state.user_stats[0].1.wins, 1);
+
+    // a compacted user can start a new game
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.user_sessions.len(), 1);
+    assert_eq!(state.user_sessions[0].1.status, SessionStatus::CheckWordWaiting);
+}
+
+#[test]
+fn test_wordle_rejects_untrusted_callers() {
+    let sys = setup();
+    let wordle = sys.get_program(WORDLE_ID).unwrap();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+
+    // user2 tries to check user1's word directly, bypassing the session attempts
+    let result = wordle.send(USER2, Action::CheckWord { user: USER1.into(), game_id: 0, word: "house".to_string() });
+    let log = Log::builder()
+        .source(WORDLE_ID)
+        .dest(USER2)
+        .payload(Event::Error(WordleError::UnauthorizedSessionProgram));
+    assert!(result.contains(&log));
+
+    // only the owner can extend the allow-list
+    let result = wordle.send(USER2, Action::AddSessionProgram { program: USER2.into() });
+    let log = Log::builder()
+        .source(WORDLE_ID)
+        .dest(USER2)
+        .payload(Event::Error(WordleError::NotOwner));
+    assert!(result.contains(&log));
+}
+
+#[test]
+fn test_wordle_unknown_game() {
+    let sys = setup();
+    let wordle = sys.get_program(WORDLE_ID).unwrap();
+    assert!(!wordle.send(USER1, Action::AddSessionProgram { program: USER3.into() }).main_failed());
+
+    // a game that was never started or has ended is an error reply, not a failing backend
+    let unknown_game = Log::builder().source(WORDLE_ID).dest(USER3).payload(Event::Error(WordleError::UnknownGame));
+    let result = wordle.send(USER3, Action::CheckWord { user: USER1.into(), game_id: 0, word: "house".to_string() });
+    assert!(!result.main_failed());
+    assert!(result.contains(&unknown_game));
+    let result = wordle.send(USER3, Action::RevealLetter { user: USER1.into(), game_id: 0, known: Vec::new() });
+    assert!(!result.main_failed());
+    assert!(result.contains(&unknown_game));
+}
+
+#[test]
+fn test_wordle_handshake() {
+    let sys = setup();
+    let wordle = sys.get_program(WORDLE_ID).unwrap();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    // anyone can ask the Wordle program what it supports
+    let info = WordleInfo {
+        protocol_version: PROTOCOL_VERSION,
+        word_lengths: vec![5],
+        banks: vec!["de".to_string(), "en".to_string(), "uk".to_string()],
+        features: vec![Feature::ValidateWord, Feature::AddBank, Feature::GameExport, Feature::RevealLetter],
+    };
+    let result = wordle.send(USER2, Action::GetInfo);
+    assert!(result.contains(&Log::builder().source(WORDLE_ID).dest(USER2).payload(Event::Info(info.clone()))));
+
+    // game-session asked for it during init
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.backends[0].info, info);
+
+    // a program that does not answer `GetInfo` is refused
+    let new_session = Program::from_file(&sys, "../target/wasm32-unknown-unknown/debug/game_session.wasm");
+    let init = GameSessionInit { wordle_program: GAME_SESSION_ID.into(), config: SessionConfig::default(), import: None };
+    assert!(new_session.send(USER1, init).main_failed());
+}
+
+#[test]
+fn test_migrate_v1_state() {
+    let sys = setup();
+
+    // a populated v1 state, as replied by `state()` of the first version of the program
+    let v1_session = |start_block, check_count, status, result| SessionV1 {
+        start_block,
+        check_count,
+        msg_ids: (MessageId::zero(), MessageId::zero()),
+        status,
+        result,
+    };
+    let v1_state = StateV1 {
+        wordle_program: WORDLE_ID.into(),
+        user_sessions: vec![
+            (USER1.into(), v1_session(5, 2, SessionStatusV1::StartGameWaiting, SessionResultV1::Win)),
+            (USER2.into(), v1_session(7, 1, SessionStatusV1::CheckWordSent, SessionResultV1::Ongoing)),
+            (USER3.into(), v1_session(7, 1, SessionStatusV1::CheckWordWaiting, SessionResultV1::Ongoing)),
+        ],
+    };
+    let (first, rest) = v1_state.user_sessions.split_at(1);
+
+    // the new program is initialized with the first chunk and receives the rest afterwards
+    let new_session = Program::from_file(&sys, "../target/wasm32-unknown-unknown/debug/game_session.wasm");
+    let init = GameSessionInit {
+        wordle_program: WORDLE_ID.into(),
+        config: SessionConfig::default(),
+        import: Some(StateChunk::V1(StateV1 { wordle_program: WORDLE_ID.into(), user_sessions: first.to_vec() })),
+    };
+    assert!(!new_session.send(USER1, init).main_failed());
+
+    let result = new_session.send(USER1, SessionAction::StartGame { bank: None });
+    let log = Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::ImportInProgress));
+    assert!(result.contains(&log));
+
+    let chunk = StateChunk::V1(StateV1 { wordle_program: WORDLE_ID.into(), user_sessions: rest.to_vec() });
+    let result = new_session.send(USER2, SessionAction::ImportState { chunk: chunk.clone() });
+    let log = Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::NotOwner));
+    assert!(result.contains(&log));
+    let result = new_session.send(USER1, SessionAction::ImportState { chunk });
+    let log = Log::builder().dest(USER1).payload(SessionEvent::StateImported { version: 1 });
+    assert!(result.contains(&log));
+    assert!(!new_session.send(USER1, SessionAction::FinishImport).main_failed());
+
+    let state: State = new_session.read_state(b"").unwrap();
+    assert_eq!(state.version, STATE_VERSION);
+    assert!(!state.importing);
+    assert_eq!(state.user_sessions.len(), 3);
+    assert_eq!(state.user_stats.len(), 3);
+
+    let user1: ActorId = USER1.into();
+    let (_, session1) = state.user_sessions.iter().find(|(user, _)| *user == user1).unwrap();
+    assert_eq!(session1.last_active_block, 5);
+    assert_eq!(session1.check_count, 2);
+    assert_eq!(session1.result, SessionResult::Win);
+    let (_, stats1) = state.user_stats.iter().find(|(user, _)| *user == user1).unwrap();
+    assert_eq!(stats1.games_played, 1);
+    assert_eq!(stats1.wins, 1);
+
+    // the message user2 was waiting on belonged to the old program, so the game is void
+    let user2: ActorId = USER2.into();
+    let (_, session2) = state.user_sessions.iter().find(|(user, _)| *user == user2).unwrap();
+    assert_eq!(session2.status, SessionStatus::StartGameWaiting);
+    assert_eq!(session2.result, SessionResult::Void);
+
+    // user3's game goes on, under a timeout scheduled by the new program
+    let user3: ActorId = USER3.into();
+    let (_, session3) = state.user_sessions.iter().find(|(user, _)| *user == user3).unwrap();
+    assert_eq!(session3.status, SessionStatus::CheckWordWaiting);
+    assert!(session3.is_open());
+
+    // the migrated state is exported as the current version, sessions in store order first
+    let result = new_session.send(USER1, SessionAction::ExportState { cursor: 0, limit: 1 });
+    let chunk = StateChunkV3 {
+        user_sessions: vec![(user1, session1.clone())],
+        user_stats: Vec::new(),
+    };
+    let log = Log::builder()
+        .dest(USER1)
+        .payload(SessionEvent::StateExported { chunk: StateChunk::V3(chunk), next_cursor: Some(1) });
+    assert!(result.contains(&log));
+
+    // a zero limit is raised to one, so a paging client can't get stuck on a cursor
+    let result = new_session.send(USER1, SessionAction::ExportState { cursor: 0, limit: 0 });
+    assert!(result.contains(&log));
+
+    sys.spend_blocks(200);
+    let state: State = new_session.read_state(b"").unwrap();
+    let (_, session3) = state.user_sessions.iter().find(|(user, _)| *user == user3).unwrap();
+    assert_eq!(session3.result, SessionResult::TimedOut);
+}
+
+#[test]
+fn test_import_without_timeout_gas() {
+    let sys = setup();
+    let new_session = Program::from_file(&sys, "../target/wasm32-unknown-unknown/debug/game_session.wasm");
+    let empty = StateChunk::V1(StateV1 { wordle_program: WORDLE_ID.into(), user_sessions: Vec::new() });
+    let init = GameSessionInit { wordle_program: WORDLE_ID.into(), config: SessionConfig::default(), import: Some(empty) };
+    assert!(!new_session.send(USER1, init).main_failed());
+
+    // every open game needs gas reserved for its timeout, a chunk short of it is refused as a whole
+    let open = SessionV1 {
+        start_block: 0,
+        check_count: 1,
+        msg_ids: (MessageId::zero(), MessageId::zero()),
+        status: SessionStatusV1::CheckWordWaiting,
+        result: SessionResultV1::Ongoing,
+    };
+    let user_sessions = (100..120u64).map(|user| (user.into(), open.clone())).collect();
+    let chunk = StateChunk::V1(StateV1 { wordle_program: WORDLE_ID.into(), user_sessions });
+    let result = new_session.send_with_gas(USER1, SessionAction::ImportState { chunk }, 20_000_000_000, 0);
+    assert!(!result.main_failed());
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::NotEnoughGasForTimeout))));
+
+    let state: State = new_session.read_state(b"").unwrap();
+    assert!(state.user_sessions.is_empty());
+    assert_eq!(state.gas_metrics.active_reservations, 0);
+    assert_eq!(state.gas_metrics.reserved_gas, 0);
+}
+
+#[test]
+fn test_export_import_round_trip() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    // the store order differs from the key order: user2's game 0, user1's game 1, user2's game 2
+    assert!(!game_session.send(USER2, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::CheckWord { game_id: 0, word: "house".to_string() }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 1, word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::Forfeit { game_id: 1 }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::Forfeit { game_id: 2 }).main_failed());
+
+    let export = |program: &Program| -> Vec<u8> {
+        let result = program.send(USER1, SessionAction::ExportState { cursor: 0, limit: MAX_EXPORT_PAGE });
+        let log = result.log().iter().find(|log| log.destination() == USER1.into()).expect("no reply");
+        log.payload().to_vec()
+    };
+    let exported = export(&game_session);
+    let SessionEvent::StateExported { chunk, next_cursor: None } = SessionEvent::decode(&mut &exported[..]).unwrap() else {
+        panic!("unexpected export reply");
+    };
+
+    // a program importing the chunk holds the sessions in the same order, and exports the same bytes
+    let new_session = Program::from_file(&sys, "../target/wasm32-unknown-unknown/debug/game_session.wasm");
+    let init = GameSessionInit { wordle_program: WORDLE_ID.into(), config: SessionConfig::default(), import: Some(chunk) };
+    assert!(!new_session.send(USER1, init).main_failed());
+    assert!(!new_session.send(USER1, SessionAction::FinishImport).main_failed());
+    assert_eq!(export(&new_session), exported);
+
+    let state: State = game_session.read_state(b"").unwrap();
+    let imported: State = new_session.read_state(b"").unwrap();
+    let keys: Vec<SessionKey> = imported.user_sessions.iter().map(|(user, session)| (*user, session.game_id)).collect();
+    assert_eq!(keys, vec![(USER2.into(), 0), (USER1.into(), 1), (USER2.into(), 2)]);
+    assert_eq!(imported.user_sessions, state.user_sessions);
+}
+
+#[test]
+fn test_export_import_pages() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    // sessions of both users interleave in the store
+    for user in [USER2, USER1, USER2, USER1, USER2] {
+        assert!(!game_session.send(user, SessionAction::StartGame { bank: None }).main_failed());
+    }
+    assert!(!game_session.send(USER2, SessionAction::Forfeit { game_id: 0 }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 1, word: "house".to_string() }).main_failed());
+
+    let export = |program: &Program, cursor: u32, limit: u32| -> (Vec<u8>, Option<u32>) {
+        let result = program.send(USER1, SessionAction::ExportState { cursor, limit });
+        let log = result.log().iter().find(|log| log.destination() == USER1.into()).expect("no reply");
+        let SessionEvent::StateExported { chunk, next_cursor } = SessionEvent::decode(&mut log.payload()).unwrap() else {
+            panic!("unexpected export reply");
+        };
+        (chunk.encode(), next_cursor)
+    };
+    let (whole, next_cursor) = export(&game_session, 0, MAX_EXPORT_PAGE);
+    assert_eq!(next_cursor, None);
+
+    // pages of two, imported one after the other, rebuild the same store
+    let mut pages = Vec::new();
+    let mut cursor = Some(0);
+    while let Some(from) = cursor {
+        let (page, next_cursor) = export(&game_session, from, 2);
+        pages.push(StateChunk::decode(&mut &page[..]).unwrap());
+        cursor = next_cursor;
+    }
+    assert_eq!(pages.len(), 4);
+    let new_session = Program::from_file(&sys, "../target/wasm32-unknown-unknown/debug/game_session.wasm");
+    let mut pages = pages.into_iter();
+    let init = GameSessionInit { wordle_program: WORDLE_ID.into(), config: SessionConfig::default(), import: pages.next() };
+    assert!(!new_session.send(USER1, init).main_failed());
+    for chunk in pages {
+        let result = new_session.send(USER1, SessionAction::ImportState { chunk });
+        assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::StateImported { version: STATE_VERSION })));
+    }
+    assert!(!new_session.send(USER1, SessionAction::FinishImport).main_failed());
+    assert_eq!(export(&new_session, 0, MAX_EXPORT_PAGE).0, whole);
+
+    let state: State = game_session.read_state(b"").unwrap();
+    let imported: State = new_session.read_state(b"").unwrap();
+    let keys = |state: &State| -> Vec<SessionKey> {
+        state.user_sessions.iter().map(|(user, session)| (*user, session.game_id)).collect()
+    };
+    assert_eq!(keys(&imported), keys(&state));
+    assert_eq!(imported.user_stats, state.user_stats);
+}
+
+#[test]
+fn test_export_wordle_games() {
+    let sys = setup();
+    let wordle = sys.get_program(WORDLE_ID).unwrap();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::StartGame { bank: None }).main_failed());
+
+    // a zero limit is raised to one, so paging through the games always gets to the end
+    let export = |cursor: u32| -> (Vec<(ActorId, GameId)>, Option<u32>) {
+        let result = wordle.send(USER1, Action::ExportGames { cursor, limit: 0 });
+        let log = result.log().iter().find(|log| log.destination() == USER1.into()).expect("no reply");
+        let Event::GamesExported { games, next_cursor } = Event::decode(&mut log.payload()).expect("invalid reply") else {
+            panic!("unexpected wordle reply");
+        };
+        (games.migrate().into_iter().map(|(key, _)| key).collect(), next_cursor)
+    };
+    assert_eq!(export(0), (vec![(USER1.into(), 0)], Some(1)));
+    assert_eq!(export(1), (vec![(USER2.into(), 1)], None));
+}
+
+#[test]
+fn test_pause_and_emergency_stop() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    // user1 has a game in progress when the owner pauses the program
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    let result = game_session.send(USER2, SessionAction::Pause);
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::NotOwner))));
+    assert!(!game_session.send(USER1, SessionAction::Pause).main_failed());
+
+    let result = game_session.send(USER2, SessionAction::StartGame { bank: None });
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::Paused))));
+    let result = game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "human".to_string() });
+    assert!(!result.main_failed());
+
+    // ownership moves to user2, who voids the ongoing game
+    assert!(!game_session.send(USER1, SessionAction::TransferOwnership { new_owner: USER2.into() }).main_failed());
+    let result = game_session.send(USER2, SessionAction::EmergencyStop);
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::EmergencyStopped { voided: 1 })));
+    let game_over = Log::builder()
+        .source(GAME_SESSION_ID)
+        .dest(USER1)
+        .payload(SessionEvent::GameOver { game_id: 0, result: SessionResult::Void });
+    assert!(result.contains(&game_over));
+
+    let state: State = game_session.read_state(b"").unwrap();
+    assert!(state.paused);
+    assert_eq!(state.owner, USER2.into());
+    let user1: ActorId = USER1.into();
+    let (_, session) = state.user_sessions.iter().find(|(user, _)| *user == user1).unwrap();
+    assert_eq!(session.result, SessionResult::Void);
+    assert_eq!(session.status, SessionStatus::StartGameWaiting);
+    assert!(state.user_stats.is_empty());
+}
+
+#[test]
+fn test_emergency_stop_in_batches() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    // more open games than one run voids
+    let users: Vec<u64> = (100..105).collect();
+    for user in &users {
+        for _ in 0..MAX_OPEN_GAMES {
+            assert!(!game_session.send(*user, SessionAction::StartGame { bank: None }).main_failed());
+        }
+    }
+    let open = users.len() * MAX_OPEN_GAMES;
+    assert!(open > EMERGENCY_STOP_BATCH);
+
+    // the first batch is voided right away, the program sends itself the rest
+    let result = game_session.send(USER1, SessionAction::EmergencyStop);
+    assert!(!result.main_failed());
+    let stopped = Log::builder().dest(USER1).payload(SessionEvent::EmergencyStopped { voided: EMERGENCY_STOP_BATCH as u32 });
+    assert!(result.contains(&stopped));
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.user_sessions.len(), open);
+    assert!(state.user_sessions.iter().all(|(_, session)| session.result == SessionResult::Void));
+    for user in &users {
+        let user_id: ActorId = (*user).into();
+        for (_, session) in state.user_sessions.iter().filter(|(owner, _)| *owner == user_id) {
+            let game_over = Log::builder().dest(*user).payload(SessionEvent::GameOver { game_id: session.game_id, result: SessionResult::Void });
+            assert!(result.contains(&game_over));
+        }
+    }
+}
+
+#[test]
+fn test_timeout_gas_reservation() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    // the timeout of a started game is paid from a reservation, held until the game ends
+    sys.mint_to(GAME_SESSION_ID, 1_000_000_000_000_000);
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::StartGame { bank: None }).main_failed());
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.gas_metrics.active_reservations, 2);
+    assert!(state.gas_metrics.reserved_gas > 0);
+    assert_eq!(state.gas_metrics.balance, sys.balance_of(GAME_SESSION_ID));
+    assert!(state.gas_metrics.balance > 0);
+    assert!(state.user_sessions.iter().all(|(_, session)| session.timeout_reservation.is_some()));
+    let reserved_per_game = state.gas_metrics.reserved_gas / 2;
+
+    // a game won before its timeout gives the gas back
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "house".to_string() }).main_failed());
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.gas_metrics.active_reservations, 1);
+    assert_eq!(state.gas_metrics.reserved_gas, reserved_per_game);
+    assert_eq!(state.gas_metrics.spent_gas, 0);
+    assert_eq!(state.user_sessions[0].1.timeout_reservation, None);
+
+    // the other reservation is spent once the timeout has run
+    sys.spend_blocks(200);
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.gas_metrics.active_reservations, 0);
+    assert_eq!(state.gas_metrics.reserved_gas, 0);
+    assert_eq!(state.gas_metrics.spent_gas, reserved_per_game);
+    assert_eq!(state.user_sessions[1].1.result, SessionResult::TimedOut);
+    assert_eq!(state.user_sessions[1].1.timeout_reservation, None);
+}
+
+#[test]
+fn test_timeout_without_balance() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    // the program holds no value at all, the players are told from the reservations
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    assert_eq!(sys.balance_of(GAME_SESSION_ID), 0);
+
+    let results = sys.spend_blocks(200);
+    for game_id in [0, 1] {
+        let timed_out = Log::builder().dest(USER1).payload(SessionEvent::GameOver { game_id, result: SessionResult::TimedOut });
+        assert!(results.iter().any(|result| result.contains(&timed_out)));
+    }
+    let state: State = game_session.read_state(b"").unwrap();
+    assert!(state.user_sessions.iter().all(|(_, session)| session.result == SessionResult::TimedOut));
+}
+
+#[test]
+fn test_keyboard_letter_states() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+    let letter = |c: char| Alphabet::Latin.index_of(c).unwrap();
+
+    // "human" against "house": 'h' is correct, 'u' is present, 'm', 'a' and 'n' are absent
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    let result = game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "human".to_string() });
+    let mut keyboard = empty_keyboard(Alphabet::Latin);
+    keyboard[letter('h')] = LetterState::Correct;
+    keyboard[letter('u')] = LetterState::Present;
+    keyboard[letter('m')] = LetterState::Absent;
+    keyboard[letter('a')] = LetterState::Absent;
+    keyboard[letter('n')] = LetterState::Absent;
+    let log = Log::builder().dest(USER1).payload(SessionEvent::WordChecked {
+        game_id: 0,
+        correct_positions: vec![0],
+        contained_in_word: vec![1],
+        keyboard: keyboard.clone(),
+    });
+    assert!(result.contains(&log));
+
+    // "horse" moves 'o', 's' and 'e' to correct, 'r' is absent, and 'u' stays present
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "horse".to_string() }).main_failed());
+    keyboard[letter('o')] = LetterState::Correct;
+    keyboard[letter('r')] = LetterState::Absent;
+    keyboard[letter('s')] = LetterState::Correct;
+    keyboard[letter('e')] = LetterState::Correct;
+
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.user_sessions[0].1.keyboard, keyboard);
+}
+
+#[test]
+fn test_guesses_are_normalized() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+
+    // four letters and letters outside the bank's alphabet are rejected: a Cyrillic 'о', and
+    // the 'u' with a combining diaeresis, which NFC composes to 'ü', four letters in all
+    let invalid = Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::Wordle(WordleError::InvalidWord)));
+    for word in ["hous", "hоuse", "hou\u{0308}se"] {
+        let result = game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: word.to_string() });
+        assert!(!result.main_failed());
+        assert!(result.contains(&invalid));
+    }
+
+    // guesses are case folded before scoring
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "HOUSE".to_string() }).main_failed());
+
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.user_sessions[0].1.check_count, 1);
+    assert_eq!(state.user_sessions[0].1.result, SessionResult::Win);
+}
+
+#[test]
+fn test_decomposed_guesses() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+    let reply = |result: &gtest::RunResult| -> SessionEvent {
+        let log = result.log().iter().find(|log| log.destination() == USER1.into()).expect("no reply");
+        SessionEvent::decode(&mut log.payload()).expect("invalid reply")
+    };
+
+    // in a bank with diacritics, a decomposed guess is composed and scored like the precomposed one
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: Some("de".to_string()) }).main_failed());
+    let decomposed = reply(&game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "ba\u{0308}ume".to_string() }));
+    let precomposed = reply(&game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "bäume".to_string() }));
+    assert!(matches!(decomposed, SessionEvent::WordChecked { .. }), "{:?}", decomposed);
+    assert_eq!(decomposed, precomposed);
+
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.user_sessions[0].1.check_count, 2);
+}
+
+#[test]
+fn test_word_banks() {
+    let sys = setup();
+    let wordle = sys.get_program(WORDLE_ID).unwrap();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    // user1 wins in the default bank, stats and leaderboards are kept per bank
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "house".to_string() }).main_failed());
+
+    let wordle_state: WordleState = wordle.read_state(b"").unwrap();
+    let banks: Vec<(String, Alphabet, u32, u32)> = wordle_state.banks
+        .into_iter()
+        .map(|bank| (bank.name, bank.alphabet, bank.answers, bank.dictionary))
+        .collect();
+    assert_eq!(banks, vec![
+        ("de".to_string(), Alphabet::LatinDiacritics, 3, 6),
+        ("en".to_string(), Alphabet::Latin, 3, 8),
+        ("uk".to_string(), Alphabet::Cyrillic, 3, 6),
+    ]);
+
+    let result = game_session.send(USER1, SessionAction::StartGame { bank: Some("fr".to_string()) });
+    let log = Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::Wordle(WordleError::UnknownBank)));
+    assert!(result.contains(&log));
+
+    // user2 plays in the Ukrainian bank, with its own alphabet and dictionary. The failed
+    // start used up game id 1.
+    let result = game_session.send(USER2, SessionAction::StartGame { bank: Some("uk".to_string()) });
+    let log = Log::builder().dest(USER2).payload(SessionEvent::GameStarted { game_id: 2, alphabet: Alphabet::Cyrillic });
+    assert!(result.contains(&log));
+    let result = game_session.send(USER2, SessionAction::CheckWord { game_id: 2, word: "ЯБЛУК".to_string() });
+    let log = Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::Wordle(WordleError::NotInDictionary)));
+    assert!(result.contains(&log));
+    assert!(!game_session.send(USER2, SessionAction::CheckWord { game_id: 2, word: "Слово".to_string() }).main_failed());
+
+    let state: State = game_session.read_state(b"").unwrap();
+    let user2: ActorId = USER2.into();
+    let (_, session2) = state.user_sessions.iter().find(|(user, _)| *user == user2).unwrap();
+    assert_eq!(session2.bank, "uk");
+    assert_eq!(session2.check_count, 1);
+    assert_eq!(session2.keyboard.len(), Alphabet::Cyrillic.letters().len());
+
+    let (_, stats1) = &state.user_stats[0];
+    assert_eq!(stats1.banks["en"].wins, 1);
+    assert_eq!(state.leaderboards.len(), 1);
+    assert_eq!(state.leaderboards[0].0, "en");
+    let points = stats1.banks["en"].points.total;
+    assert_eq!(state.leaderboards[0].1, vec![LeaderboardEntry { user: USER1.into(), points, wins: 1, games_played: 1 }]);
+}
+
+#[test]
+fn test_word_packs() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+    let submit = SessionAction::SubmitWordPack {
+        name: "gear".to_string(),
+        alphabet: Alphabet::Latin,
+        words: vec!["Actor".to_string(), "vara".to_string()],
+    };
+
+    // every word has to be valid in the pack's alphabet
+    let result = game_session.send(USER2, submit);
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::InvalidWordPack))));
+    let submit = SessionAction::SubmitWordPack {
+        name: "gear".to_string(),
+        alphabet: Alphabet::Latin,
+        words: vec!["Actor".to_string()],
+    };
+    let result = game_session.send(USER2, submit);
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::WordPackSubmitted { name: "gear".to_string() })));
+
+    // pending packs can't be played, and only moderators review them
+    let result = game_session.send(USER1, SessionAction::StartGame { bank: Some("gear".to_string()) });
+    let log = Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::Wordle(WordleError::UnknownBank)));
+    assert!(result.contains(&log));
+    let result = game_session.send(USER2, SessionAction::ReviewWordPack { name: "gear".to_string(), approve: true });
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::NotModerator))));
+
+    const MODERATOR: u64 = 12;
+    assert!(!game_session.send(USER1, SessionAction::AddModerator { moderator: MODERATOR.into() }).main_failed());
+    assert!(!game_session.send(MODERATOR, SessionAction::ReviewWordPack { name: "gear".to_string(), approve: true }).main_failed());
+
+    // user1 plays the approved pack, and its creator is credited
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: Some("gear".to_string()) }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 1, word: "actor".to_string() }).main_failed());
+
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.moderators, vec![MODERATOR.into()]);
+    assert_eq!(state.word_packs, vec![WordPackInfo {
+        name: "gear".to_string(),
+        creator: USER2.into(),
+        alphabet: Alphabet::Latin,
+        size: 1,
+        status: WordPackStatus::Approved,
+        play_count: 1,
+    }]);
+    let user2: ActorId = USER2.into();
+    let (_, creator_stats) = state.user_stats.iter().find(|(user, _)| *user == user2).unwrap();
+    assert_eq!(creator_stats.pack_plays, 1);
+    let user1: ActorId = USER1.into();
+    let (_, player_stats) = state.user_stats.iter().find(|(user, _)| *user == user1).unwrap();
+    assert_eq!(player_stats.banks["gear"].wins, 1);
+}
+
+#[test]
+fn test_friend_challenge() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+    let challenge = |friend: u64, word: &str| SessionAction::ChallengeFriend { friend: friend.into(), word: word.to_string(), bank: None };
+
+    // the word has to be in the dictionary, and players can't challenge themselves
+    let result = game_session.send(USER1, challenge(USER2, "zzzzz"));
+    let log = Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::Wordle(WordleError::NotInDictionary)));
+    assert!(result.contains(&log));
+    let result = game_session.send(USER1, challenge(USER1, "mouse"));
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::InvalidChallenge))));
+
+    // only the hash of the word is in the state and the replies until the game is over
+    let result = game_session.send(USER1, challenge(USER2, "Mouse"));
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.challenges.len(), 1);
+    let info = state.challenges[0].clone();
+    assert_eq!(info.status, ChallengeStatus::Pending);
+    assert_eq!(info.revealed, None);
+    let created = SessionEvent::ChallengeCreated { challenge_id: info.challenge_id, word_hash: info.word_hash };
+    assert!(result.contains(&Log::builder().dest(USER1).payload(created)));
+    let received = SessionEvent::ChallengeReceived { challenge_id: info.challenge_id, challenger: USER1.into(), word_hash: info.word_hash };
+    assert!(result.contains(&Log::builder().dest(USER2).payload(received)));
+
+    // only the challenged friend can accept
+    let accept = SessionAction::AcceptChallenge { challenge_id: info.challenge_id };
+    let result = game_session.send(USER1, accept.clone());
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::UnknownChallenge))));
+    let result = game_session.send(USER2, accept);
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::GameStarted { game_id: 0, alphabet: Alphabet::Latin })));
+
+    assert!(!game_session.send(USER2, SessionAction::CheckWord { game_id: 0, word: "house".to_string() }).main_failed());
+    let result = game_session.send(USER2, SessionAction::CheckWord { game_id: 0, word: "mouse".to_string() });
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::GameOver { game_id: 0, result: SessionResult::Win })));
+    let finished = SessionEvent::ChallengeFinished { challenge_id: info.challenge_id, result: SessionResult::Win };
+    assert!(result.contains(&Log::builder().dest(USER1).payload(finished.clone())));
+    assert!(result.contains(&Log::builder().dest(USER2).payload(finished)));
+
+    let state: State = game_session.read_state(b"").unwrap();
+    let info = &state.challenges[0];
+    assert_eq!(info.status, ChallengeStatus::Finished(SessionResult::Win));
+    let (word, salt) = info.revealed.clone().unwrap();
+    assert_eq!(word, "mouse");
+    assert_eq!(challenge_commitment(&salt, &word), info.word_hash);
+
+    // the challenger picked the word, so the win counts for user2 but is neither scored nor ranked
+    let user2: ActorId = USER2.into();
+    let (_, session) = state.user_sessions.iter().find(|(user, _)| *user == user2).unwrap();
+    assert_eq!(session.score, None);
+    let (_, stats) = state.user_stats.iter().find(|(user, _)| *user == user2).unwrap();
+    assert_eq!((stats.games_played, stats.wins), (1, 1));
+    assert!(stats.banks.is_empty());
+    assert!(state.leaderboards.is_empty());
+}
+
+/// `(seq, event)` of the log entries an indexer received from one message run.
+fn indexed(result: &gtest::RunResult, indexer: u64) -> Vec<(u64, SessionLogEvent)> {
+    result.log()
+        .iter()
+        .filter(|log| log.destination() == indexer.into())
+        .map(|log| SessionLogEntry::decode(&mut log.payload()).expect("invalid log entry"))
+        .map(|entry| (entry.seq, entry.event))
+        .collect()
+}
+
+#[test]
+fn test_event_log() {
+    const INDEXER: u64 = 20;
+    let sys = setup_with_config(SessionConfig { indexer: INDEXER.into(), ..SessionConfig::default() });
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    let result = game_session.send(USER1, SessionAction::StartGame { bank: None });
+    let started = SessionLogEvent::GameStarted { bank: "en".to_string(), challenge: None, mode: GameMode::Ranked };
+    assert_eq!(indexed(&result, INDEXER), vec![(0, started)]);
+
+    let result = game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "Human".to_string() });
+    let guess = SessionLogEvent::Guess { word: "human".to_string(), correct_positions: vec![0], contained_in_word: vec![1] };
+    assert_eq!(indexed(&result, INDEXER), vec![(1, guess)]);
+
+    let result = game_session.send(USER1, SessionAction::Forfeit { game_id: 0 });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::GameOver { game_id: 0, result: SessionResult::Forfeited })));
+    assert_eq!(indexed(&result, INDEXER), vec![(2, SessionLogEvent::Forfeit)]);
+    let result = game_session.send(USER1, SessionAction::Forfeit { game_id: 0 });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::NoGameInProgress))));
+
+    // only the owner moves the log to another indexer
+    let result = game_session.send(USER2, SessionAction::SetIndexer { indexer: USER2.into() });
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::NotOwner))));
+    let result = game_session.send(USER1, SessionAction::SetIndexer { indexer: ActorId::zero() });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::IndexerSet { indexer: ActorId::zero() })));
+
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.next_event_seq, 3);
+    assert_eq!(state.config.indexer, ActorId::zero());
+    let (_, stats) = &state.user_stats[0];
+    assert_eq!((stats.losses, stats.timeouts, stats.forfeits), (1, 0, 1));
+}
+
+#[test]
+fn test_paginated_state_reads() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+    let (user1, user2): (ActorId, ActorId) = (USER1.into(), USER2.into());
+
+    // user1 wins, user2 is still playing
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "house".to_string() }).main_failed());
+    sys.spend_blocks(10);
+    assert!(!game_session.send(USER2, SessionAction::StartGame { bank: None }).main_failed());
+
+    let page = |filter: SessionFilter, cursor: Option<SessionKey>, limit: u32| -> (Vec<ActorId>, Option<SessionKey>) {
+        let reply: StateReply = game_session.read_state(StateQuery::Sessions { filter, cursor, limit }).unwrap();
+        let StateReply::Sessions { sessions, next_cursor } = reply else {
+            panic!("unexpected state reply");
+        };
+        (sessions.into_iter().map(|(user, _)| user).collect(), next_cursor)
+    };
+
+    assert_eq!(page(SessionFilter::default(), None, 1), (vec![user1], Some((user1, 0))));
+    assert_eq!(page(SessionFilter::default(), Some((user1, 0)), 1), (vec![user2], None));
+    assert_eq!(page(SessionFilter::default(), None, 10), (vec![user1, user2], None));
+
+    let won = SessionFilter { result: Some(SessionResult::Win), ..SessionFilter::default() };
+    assert_eq!(page(won, None, 10), (vec![user1], None));
+    let playing = SessionFilter { status: Some(SessionStatus::CheckWordWaiting), ..SessionFilter::default() };
+    assert_eq!(page(playing, None, 10), (vec![user2], None));
+    // user2 started 10 blocks after user1
+    let middle = sys.block_height() - 5;
+    let recent = SessionFilter { start_block_from: Some(middle), ..SessionFilter::default() };
+    assert_eq!(page(recent, None, 10), (vec![user2], None));
+    let early = SessionFilter { start_block_to: Some(middle), ..SessionFilter::default() };
+    assert_eq!(page(early, None, 10), (vec![user1], None));
+
+    let reply: StateReply = game_session.read_state(StateQuery::UserSession { user: user2, game_id: 1 }).unwrap();
+    let StateReply::UserSession(Some(session)) = reply else {
+        panic!("unexpected state reply");
+    };
+    assert_eq!(session.result, SessionResult::Ongoing);
+}
+
+#[test]
+fn test_anti_abuse() {
+    let anti_abuse = AntiAbuseConfig { min_guess_interval: 5, max_games_per_day: 2, min_solve_blocks: 10, fast_solve_limit: 0 };
+    let sys = setup_with_config(SessionConfig { anti_abuse, ..SessionConfig::default() });
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    // guesses right after the start or the previous guess are rejected without using an attempt
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    let result = game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "human".to_string() });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::GuessTooSoon))));
+    sys.spend_blocks(5);
+    let result = game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "house".to_string() });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::GameOver { game_id: 0, result: SessionResult::Win })));
+
+    // the win took less than `min_solve_blocks`, and no fast solve is allowed
+    let state: State = game_session.read_state(b"").unwrap();
+    let (_, stats) = &state.user_stats[0];
+    assert_eq!(stats.fast_solves, 1);
+    assert!(stats.flagged);
+    assert_eq!(state.leaderboards, vec![("en".to_string(), vec![])]);
+
+    // a second game is allowed today, a third one is not
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::Forfeit { game_id: 1 }).main_failed());
+    let result = game_session.send(USER1, SessionAction::StartGame { bank: None });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::DailyGameLimit))));
+
+    // moderators clear flags, the owner changes the rules
+    let result = game_session.send(USER2, SessionAction::SetFlagged { user: USER1.into(), flagged: false });
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::NotModerator))));
+    assert!(!game_session.send(USER1, SessionAction::SetFlagged { user: USER1.into(), flagged: false }).main_failed());
+    let result = game_session.send(USER1, SessionAction::SetAntiAbuse { config: AntiAbuseConfig::default() });
+    let log = Log::builder().dest(USER1).payload(SessionEvent::AntiAbuseSet { config: AntiAbuseConfig::default() });
+    assert!(result.contains(&log));
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+
+    let state: State = game_session.read_state(b"").unwrap();
+    let (_, stats) = &state.user_stats[0];
+    assert!(!stats.flagged);
+    assert_eq!(stats.games_today, 3);
+    let points = state.user_sessions[0].1.score.unwrap().points as u64;
+    let entry = LeaderboardEntry { user: USER1.into(), points, wins: 1, games_played: 2 };
+    assert_eq!(state.leaderboards, vec![("en".to_string(), vec![entry])]);
+}
+
+#[test]
+fn test_concurrent_games() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+    let user1: ActorId = USER1.into();
+
+    // every game gets its own id, and guesses only count towards the game they name
+    let result = game_session.send(USER1, SessionAction::StartGame { bank: None });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::GameStarted { game_id: 0, alphabet: Alphabet::Latin })));
+    let result = game_session.send(USER1, SessionAction::StartGame { bank: None });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::GameStarted { game_id: 1, alphabet: Alphabet::Latin })));
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 1, word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 1, word: "horse".to_string() }).main_failed());
+    let result = game_session.send(USER1, SessionAction::CheckWord { game_id: 7, word: "human".to_string() });
+    assert!(!result.main_failed());
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::NoGameInProgress))));
+
+    let reply: StateReply = game_session.read_state(StateQuery::OpenGames { user: user1 }).unwrap();
+    let StateReply::OpenGames(games) = reply else {
+        panic!("unexpected state reply");
+    };
+    let games: Vec<(GameId, u8)> = games.iter().map(|session| (session.game_id, session.check_count)).collect();
+    assert_eq!(games, vec![(0, 0), (1, 2)]);
+
+    // a forfeited game is no longer open, the other one goes on
+    let result = game_session.send(USER1, SessionAction::Forfeit { game_id: 0 });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::GameOver { game_id: 0, result: SessionResult::Forfeited })));
+    let reply: StateReply = game_session.read_state(StateQuery::OpenGames { user: user1 }).unwrap();
+    let StateReply::OpenGames(games) = reply else {
+        panic!("unexpected state reply");
+    };
+    assert_eq!(games.iter().map(|session| session.game_id).collect::<Vec<_>>(), vec![1]);
+
+    // only `MAX_OPEN_GAMES` games can be in progress at once
+    for _ in 1..MAX_OPEN_GAMES {
+        assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    }
+    let result = game_session.send(USER1, SessionAction::StartGame { bank: None });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::TooManyOpenGames))));
+
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.user_sessions.len(), MAX_OPEN_GAMES + 1);
+    assert_eq!(state.user_stats[0].1.games_played, 1);
+}
+
+#[test]
+fn test_backend_failover() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+    let wordle = sys.get_program(WORDLE_ID).unwrap();
+    let wordle2 = Program::from_file(&sys, "../target/wasm32-unknown-unknown/debug/wordle.wasm");
+    assert!(!wordle2.send(USER1, WordleInit { session_programs: vec![GAME_SESSION_ID.into()] }).main_failed());
+
+    // only the owner registers backends, and each one only once
+    let result = game_session.send(USER2, SessionAction::AddBackend { program: WORDLE2_ID.into() });
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::NotOwner))));
+    let result = game_session.send(USER1, SessionAction::AddBackend { program: WORDLE2_ID.into() });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::BackendAdded { program: WORDLE2_ID.into() })));
+    let result = game_session.send(USER1, SessionAction::AddBackend { program: WORDLE2_ID.into() });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::BackendExists))));
+
+    // new games take turns on the backends
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::StartGame { bank: None }).main_failed());
+    let state: State = game_session.read_state(b"").unwrap();
+    let backends: Vec<ActorId> = state.user_sessions.iter().map(|(_, session)| session.backend).collect();
+    assert_eq!(backends, vec![WORDLE_ID.into(), WORDLE2_ID.into()]);
+
+    // a paused backend fails the start, the game moves on to the other one
+    assert!(!wordle.send(USER1, Action::Pause).main_failed());
+    let result = game_session.send(USER2, SessionAction::StartGame { bank: None });
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::GameStarted { game_id: 2, alphabet: Alphabet::Latin })));
+    let state: State = game_session.read_state(b"").unwrap();
+    let user2: ActorId = USER2.into();
+    let (_, session) = state.user_sessions.iter().find(|(user, session)| *user == user2 && session.game_id == 2).unwrap();
+    assert_eq!(session.backend, WORDLE2_ID.into());
+    assert_eq!(session.start_attempts, 2);
+    assert_eq!(state.backends[0].failures, 1);
+    assert_eq!(state.backends[1].failures, 0);
+
+    // a program that never answers `GetInfo` is refused once the wait runs out
+    assert!(!game_session.send(USER1, SessionAction::AddBackend { program: USER2.into() }).main_failed());
+    let results = sys.spend_blocks(BACKEND_REPLY_TIMEOUT);
+    let refused = Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::IncompatibleBackend));
+    assert!(results.iter().any(|result| result.contains(&refused)));
+
+    // a removed backend gets no new games
+    let result = game_session.send(USER1, SessionAction::RemoveBackend { program: WORDLE2_ID.into() });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::BackendRemoved { program: WORDLE2_ID.into() })));
+    let result = game_session.send(USER1, SessionAction::RemoveBackend { program: WORDLE2_ID.into() });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::UnknownBackend))));
+    let reply: StateReply = game_session.read_state(StateQuery::Backends).unwrap();
+    let StateReply::Backends(backends) = reply else {
+        panic!("unexpected state reply");
+    };
+    assert_eq!(backends.iter().map(|backend| backend.program).collect::<Vec<_>>(), vec![WORDLE_ID.into()]);
+}
+
+/// What `USER3` answers to `GetInfo` when it stands in for a backend. Its messages wait in
+/// its mailbox, so it can answer them as late as a test needs.
+fn mailbox_backend_info() -> Event {
+    Event::Info(WordleInfo {
+        protocol_version: PROTOCOL_VERSION,
+        word_lengths: vec![5],
+        banks: vec!["en".to_string()],
+        features: Vec::new(),
+    })
+}
+
+#[test]
+fn test_late_backend_reply() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+    let mailbox = sys.get_mailbox(USER3);
+    assert!(!game_session.send(USER1, SessionAction::AddBackend { program: USER3.into() }).main_failed());
+    let get_info = Log::builder().source(GAME_SESSION_ID).dest(USER3).payload(Action::GetInfo);
+    let result = mailbox.reply(get_info, mailbox_backend_info(), 0).expect("no `GetInfo` in the mailbox");
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::BackendAdded { program: USER3.into() })));
+    assert!(!game_session.send(USER1, SessionAction::RemoveBackend { program: WORDLE_ID.into() }).main_failed());
+
+    // the only backend does not answer in time, so the game never starts
+    assert!(!game_session.send(USER2, SessionAction::StartGame { bank: None }).main_failed());
+    let results = sys.spend_blocks(BACKEND_REPLY_TIMEOUT);
+    let unavailable = Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::BackendUnavailable));
+    assert!(results.iter().any(|result| result.contains(&unavailable)));
+
+    // its late `GameStarted` is stale, and the game it started there is ended
+    let start = Action::StartGame { user: USER2.into(), game_id: 0, bank: "en".to_string(), word: None };
+    let start = Log::builder().source(GAME_SESSION_ID).dest(USER3).payload(start);
+    let started = Event::GameStarted { user: USER2.into(), game_id: 0, alphabet: Alphabet::Latin };
+    let result = mailbox.reply(start, started, 0).expect("no `StartGame` in the mailbox");
+    assert!(!result.main_failed());
+    let end_game = Action::EndGame { user: USER2.into(), game_id: 0 };
+    assert!(result.contains(&Log::builder().source(GAME_SESSION_ID).dest(USER3).payload(end_game)));
+
+    let state: State = game_session.read_state(b"").unwrap();
+    assert!(state.user_sessions.is_empty());
+}
+
+#[test]
+fn test_daily_limit_counts_pending_starts() {
+    let anti_abuse = AntiAbuseConfig { max_games_per_day: 2, ..AntiAbuseConfig::default() };
+    let sys = setup_with_config(SessionConfig { anti_abuse, ..SessionConfig::default() });
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+    let mailbox = sys.get_mailbox(USER3);
+    assert!(!game_session.send(USER1, SessionAction::AddBackend { program: USER3.into() }).main_failed());
+    let get_info = Log::builder().source(GAME_SESSION_ID).dest(USER3).payload(Action::GetInfo);
+    assert!(mailbox.reply(get_info, mailbox_backend_info(), 0).is_ok());
+    assert!(!game_session.send(USER1, SessionAction::RemoveBackend { program: WORDLE_ID.into() }).main_failed());
+
+    // starts waiting for the backend already count towards the daily cap
+    assert!(!game_session.send(USER2, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::StartGame { bank: None }).main_failed());
+    let result = game_session.send(USER2, SessionAction::StartGame { bank: None });
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::DailyGameLimit))));
+
+    // both waiting games start once the backend answers, and the cap still holds afterwards
+    for game_id in [0, 1] {
+        let start = Action::StartGame { user: USER2.into(), game_id, bank: "en".to_string(), word: None };
+        let start = Log::builder().source(GAME_SESSION_ID).dest(USER3).payload(start);
+        let started = Event::GameStarted { user: USER2.into(), game_id, alphabet: Alphabet::Latin };
+        let result = mailbox.reply(start, started, 0).expect("no `StartGame` in the mailbox");
+        assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::GameStarted { game_id, alphabet: Alphabet::Latin })));
+    }
+    let result = game_session.send(USER2, SessionAction::StartGame { bank: None });
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::DailyGameLimit))));
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.user_sessions.len(), 2);
+}
+
+#[test]
+fn test_concurrent_backend_adds() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+    let mailbox = sys.get_mailbox(USER3);
+    let get_info = || Log::builder().source(GAME_SESSION_ID).dest(USER3).payload(Action::GetInfo);
+
+    // the second add is refused while the first one waits for the handshake
+    assert!(!game_session.send(USER1, SessionAction::AddBackend { program: USER3.into() }).main_failed());
+    let result = game_session.send(USER1, SessionAction::AddBackend { program: USER3.into() });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::BackendExists))));
+    let result = mailbox.reply(get_info(), mailbox_backend_info(), 0).expect("no `GetInfo` in the mailbox");
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::BackendAdded { program: USER3.into() })));
+    assert!(mailbox.reply(get_info(), mailbox_backend_info(), 0).is_err());
+
+    let reply: StateReply = game_session.read_state(StateQuery::Backends).unwrap();
+    let StateReply::Backends(backends) = reply else {
+        panic!("unexpected state reply");
+    };
+    assert_eq!(backends.iter().map(|backend| backend.program).collect::<Vec<_>>(), vec![WORDLE_ID.into(), USER3.into()]);
+}
+
+#[test]
+fn test_practice_mode() {
+    let anti_abuse = AntiAbuseConfig { max_games_per_day: 1, ..AntiAbuseConfig::default() };
+    let sys = setup_with_config(SessionConfig { anti_abuse, ..SessionConfig::default() });
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    // the ranked game uses up the daily cap, practice games are not capped
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    let result = game_session.send(USER1, SessionAction::StartGame { bank: None });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::Error(SessionError::DailyGameLimit))));
+    let result = game_session.send(USER1, SessionAction::StartPractice { bank: None, unlimited_guesses: true });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::GameStarted { game_id: 1, alphabet: Alphabet::Latin })));
+
+    // with unlimited guesses the game goes on past the last attempt
+    for _ in 0..8 {
+        let result = game_session.send(USER1, SessionAction::CheckWord { game_id: 1, word: "human".to_string() });
+        assert!(!result.main_failed());
+    }
+    let reply: StateReply = game_session.read_state(StateQuery::UserSession { user: USER1.into(), game_id: 1 }).unwrap();
+    let StateReply::UserSession(Some(session)) = reply else {
+        panic!("unexpected state reply");
+    };
+    assert_eq!(session.mode, GameMode::Practice { unlimited_guesses: true });
+    assert_eq!(session.check_count, 8);
+    assert_eq!(session.result, SessionResult::Ongoing);
+
+    // restarting ends the practice game in progress
+    let result = game_session.send(USER1, SessionAction::StartPractice { bank: None, unlimited_guesses: false });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::GameOver { game_id: 1, result: SessionResult::Forfeited })));
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::GameStarted { game_id: 2, alphabet: Alphabet::Latin })));
+    let result = game_session.send(USER1, SessionAction::CheckWord { game_id: 2, word: "house".to_string() });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::GameOver { game_id: 2, result: SessionResult::Win })));
+
+    // none of it shows up in the stats
+    let state: State = game_session.read_state(b"").unwrap();
+    let (_, stats) = &state.user_stats[0];
+    assert_eq!((stats.games_played, stats.wins, stats.losses, stats.games_today), (0, 0, 0, 1));
+    assert!(state.leaderboards.is_empty());
+}
+
+#[test]
+fn test_hints() {
+    let sys = setup();
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+    // hints reveal the positions no guess has found yet (the hidden word is "house" in test mode)
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    let result = game_session.send(USER1, SessionAction::RequestHint { game_id: 0 });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::HintRevealed { game_id: 0, position: 0, letter: "h".to_string() })));
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "horse".to_string() }).main_failed());
+    let result = game_session.send(USER1, SessionAction::RequestHint { game_id: 0 });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::HintRevealed { game_id: 0, position: 2, letter: "u".to_string() })));
+    let result = game_session.send(USER1, SessionAction::RequestHint { game_id: 0 });
+    let nothing_left = SessionEvent::Error(SessionError::Wordle(WordleError::NothingToReveal));
+    assert!(result.contains(&Log::builder().dest(USER1).payload(nothing_left)));
+
+    // by default every hint costs an attempt, and the last one can't be spent on a hint
+    assert!(!game_session.send(USER2, SessionAction::StartGame { bank: None }).main_failed());
+    for _ in 0..4 {
+        assert!(!game_session.send(USER2, SessionAction::CheckWord { game_id: 1, word: "human".to_string() }).main_failed());
+    }
+    assert!(!game_session.send(USER2, SessionAction::RequestHint { game_id: 1 }).main_failed());
+    let result = game_session.send(USER2, SessionAction::RequestHint { game_id: 1 });
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::NoAttemptsLeft))));
+
+    // only the owner changes the cost
+    let result = game_session.send(USER2, SessionAction::SetHintCost { cost: HintCost::ScorePenalty(10) });
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::NotOwner))));
+    let result = game_session.send(USER1, SessionAction::SetHintCost { cost: HintCost::ScorePenalty(10) });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::HintCostSet { cost: HintCost::ScorePenalty(10) })));
+
+    let state: State = game_session.read_state(b"").unwrap();
+    let (_, session) = &state.user_sessions[0];
+    assert_eq!(session.hints, vec![(0, "h".to_string()), (2, "u".to_string())]);
+    assert_eq!(session.check_count, 3);
+    assert_eq!(state.user_sessions[1].1.check_count, 5);
+
+    // the hints are counted in the stats once the game is over
+    let result = game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "house".to_string() });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::GameOver { game_id: 0, result: SessionResult::Win })));
+    let state: State = game_session.read_state(b"").unwrap();
+    let user1: ActorId = USER1.into();
+    let (_, stats) = state.user_stats.iter().find(|(user, _)| *user == user1).unwrap();
+    assert_eq!(stats.hints_used, 2);
+}
+
+#[test]
+fn test_scoring() {
+    // no speed bonus, so the points don't depend on how many blocks the games took
+    let scoring = ScoringConfig { speed_points: 0, ..ScoringConfig::default() };
+    let sys = setup_with_config(SessionConfig { scoring, hint_cost: HintCost::ScorePenalty(15), ..SessionConfig::default() });
+    let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+    let (user1, user2): (ActorId, ActorId) = (USER1.into(), USER2.into());
+
+    // a win scores its unused attempts, the first win on a word gets the whole rarity bonus
+    // and hints are taken off (the hidden word is "house" in test mode)
+    assert!(!game_session.send(USER1, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::RequestHint { game_id: 0 }).main_failed());
+    assert!(!game_session.send(USER1, SessionAction::CheckWord { game_id: 0, word: "house".to_string() }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::CheckWord { game_id: 1, word: "human".to_string() }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::CheckWord { game_id: 1, word: "house".to_string() }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::Forfeit { game_id: 2 }).main_failed());
+
+    let state: State = game_session.read_state(b"").unwrap();
+    let scores: Vec<Option<Score>> = state.user_sessions.iter().map(|(_, session)| session.score).collect();
+    assert_eq!(scores, vec![
+        Some(Score { version: 1, points: 100 + 5 * 10 + 50 - 15 }),
+        Some(Score { version: 1, points: 100 + 4 * 10 + 25 }),
+        Some(Score { version: 1, points: 0 }),
+    ]);
+    let entries: Vec<(ActorId, u64)> = state.leaderboards[0].1.iter().map(|entry| (entry.user, entry.points)).collect();
+    assert_eq!(entries, vec![(user1, 185), (user2, 165)]);
+
+    // a new formula starts a new version, the leaderboards only count points scored under it
+    let result = game_session.send(USER2, SessionAction::SetScoring { scoring });
+    assert!(result.contains(&Log::builder().dest(USER2).payload(SessionEvent::Error(SessionError::NotOwner))));
+    let scoring = ScoringConfig { win_points: 10, attempt_points: 0, rarity_points: 0, ..scoring };
+    let result = game_session.send(USER1, SessionAction::SetScoring { scoring });
+    assert!(result.contains(&Log::builder().dest(USER1).payload(SessionEvent::ScoringSet { version: 2, scoring })));
+    assert!(!game_session.send(USER2, SessionAction::StartGame { bank: None }).main_failed());
+    assert!(!game_session.send(USER2, SessionAction::CheckWord { game_id: 3, word: "house".to_string() }).main_failed());
+
+    let state: State = game_session.read_state(b"").unwrap();
+    assert_eq!(state.scoring_version, 2);
+    let entries: Vec<(ActorId, u64)> = state.leaderboards[0].1.iter().map(|entry| (entry.user, entry.points)).collect();
+    assert_eq!(entries, vec![(user2, 10), (user1, 0)]);
+    let (_, stats) = state.user_stats.iter().find(|(user, _)| *user == user2).unwrap();
+    assert_eq!(stats.points, Points { version: 2, total: 10 });
+}
+
+/// xorshift64, so runs of `test_session_state_machine` can be replayed from their seed.
+fn next_random(seed: &mut u64) -> u64 {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 7;
+    *seed ^= *seed << 17;
+    *seed
+}
+
+fn check_session_invariants(state: &State) {
+    let mut ranked = BTreeMap::<ActorId, u32>::new();
+    for (user, session) in &state.user_sessions {
+        // every message has run to completion, so nothing waits for wordle
+        assert!(matches!(session.status, SessionStatus::StartGameWaiting | SessionStatus::CheckWordWaiting), "{:?}", session);
+        assert_eq!(session.result == SessionResult::Ongoing, session.status == SessionStatus::CheckWordWaiting, "{:?}", session);
+        if !session.mode.unlimited_guesses() {
+            assert!(session.check_count <= MAX_ATTEMPTS, "{:?}", session);
+        }
+        if !session.mode.is_practice() {
+            // only finished games are recorded, open ones are not played yet
+            let finished = ranked.entry(*user).or_default();
+            if !matches!(session.result, SessionResult::Ongoing | SessionResult::Void) {
+                *finished += 1;
+            }
+        }
+    }
+    for (user, finished) in &ranked {
+        let open = state.user_sessions.iter().filter(|(owner, session)| owner == user && session.is_open() && !session.mode.is_practice());
+        assert!(open.count() <= MAX_OPEN_GAMES);
+        let (_, stats) = state.user_stats.iter().find(|(owner, _)| owner == user).unwrap();
+        assert_eq!(stats.games_played, *finished);
+        assert_eq!(stats.wins + stats.losses, *finished);
+    }
+}
+
+#[test]
+fn test_session_state_machine() {
+    let words = ["human", "horse", "house"];
+
+    for initial in [0x2545_f491_4f6c_dd1d, 0x9e37_79b9_7f4a_7c15, 0xdead_beef_cafe_f00d] {
+        let mut seed: u64 = initial;
+        let sys = setup();
+        let game_session = sys.get_program(GAME_SESSION_ID).unwrap();
+
+        for _ in 0..150 {
+            let user = [USER1, USER2][(next_random(&mut seed) % 2) as usize];
+            let state: State = game_session.read_state(b"").unwrap();
+            let user_id: ActorId = user.into();
+            let game_ids: Vec<GameId> = state.user_sessions.iter()
+                .filter(|(owner, _)| *owner == user_id)
+                .map(|(_, session)| session.game_id)
+                .collect();
+            let game_id = match game_ids.len() {
+                0 => None,
+                len => Some(game_ids[(next_random(&mut seed) % len as u64) as usize]),
+            };
+
+            let action = match (next_random(&mut seed) % 8, game_id) {
+                (0, _) | (_, None) => SessionAction::StartGame { bank: None },
+                (1, _) => SessionAction::StartPractice { bank: None, unlimited_guesses: next_random(&mut seed) % 2 == 0 },
+                (2..=4, Some(game_id)) => {
+                    let word = words[(next_random(&mut seed) % words.len() as u64) as usize].to_string();
+                    SessionAction::CheckWord { game_id, word }
+                },
+                (5, Some(game_id)) => SessionAction::RequestHint { game_id },
+                (6, Some(game_id)) => SessionAction::Forfeit { game_id },
+                (_, Some(_)) => {
+                    // long enough for games to time out now and then
+                    let results = sys.spend_blocks((next_random(&mut seed) % 120) as u32);
+                    assert!(results.iter().all(|result| !result.main_failed()), "seed {:x}", initial);
+                    check_session_invariants(&game_session.read_state(b"").unwrap());
+                    continue;
+                },
+            };
+            let result = game_session.send(user, action.clone());
+            assert!(!result.main_failed(), "seed {:x}: {:?} failed", initial, action);
+            assert!(result.contains(&Log::builder().dest(user)), "seed {:x}: no reply to {:?}", initial, action);
+            check_session_invariants(&game_session.read_state(b"").unwrap());
+        }
+    }
+}
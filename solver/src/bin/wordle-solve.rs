@@ -0,0 +1,196 @@
+//! Plays games against the Wordle and game-session programs deployed in gtest and
+//! reports how many attempts the solver needs.
+//!
+//! Build the programs first (`cargo build` in the workspace root), then run
+//! `cargo run -p wordle-solver --features cli -- --words <file>` where the file holds the
+//! words of the bank, answers and allowed guesses, one per line.
+
+use std::{env, fs, process};
+
+use game_session_io::{GameSessionInit, SessionAction, SessionConfig, SessionEvent, SessionResult};
+use gstd::prelude::*;
+use gtest::{Program, RunResult, System};
+use wordle_engine::Feedback;
+use wordle_io::WordleInit;
+use wordle_solver::{Solver, Strategy};
+
+const WORDLE_ID: u64 = 1;
+const GAME_SESSION_ID: u64 = 2;
+const PLAYER: u64 = 10;
+
+const USAGE: &str =
+    "usage: wordle-solve --words <file> [--games <n>] [--strategy entropy|minimax] \
+                     [--bank <name>] [--wasm-dir <dir>]";
+
+struct Args {
+    words: Vec<String>,
+    games: u32,
+    strategy: Strategy,
+    bank: Option<String>,
+    wasm_dir: String,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut words = None;
+    let mut games = 100;
+    let mut strategy = Strategy::Entropy;
+    let mut bank = None;
+    let mut wasm_dir = "target/wasm32-unknown-unknown/debug".to_string();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--words" => {
+                let path = value()?;
+                let file = fs::read_to_string(&path).map_err(|error| format!("{path}: {error}"))?;
+                words = Some(
+                    file.lines()
+                        .map(|line| line.trim().to_lowercase())
+                        .filter(|line| !line.is_empty())
+                        .collect(),
+                );
+            }
+            "--games" => {
+                games = value()?
+                    .parse()
+                    .map_err(|_| "--games takes a number".to_string())?
+            }
+            "--strategy" => {
+                strategy = match value()?.as_str() {
+                    "entropy" => Strategy::Entropy,
+                    "minimax" => Strategy::Minimax,
+                    other => return Err(format!("unknown strategy {other}")),
+                }
+            }
+            "--bank" => bank = Some(value()?),
+            "--wasm-dir" => wasm_dir = value()?,
+            other => return Err(format!("unknown argument {other}")),
+        }
+    }
+
+    let words = words.ok_or("--words is required".to_string())?;
+    Ok(Args {
+        words,
+        games,
+        strategy,
+        bank,
+        wasm_dir,
+    })
+}
+
+fn deploy(sys: &System, wasm_dir: &str) {
+    let wordle = Program::from_file(sys, format!("{wasm_dir}/wordle.wasm"));
+    let game_session = Program::from_file(sys, format!("{wasm_dir}/game_session.wasm"));
+    assert!(!wordle
+        .send(
+            PLAYER,
+            WordleInit {
+                session_programs: vec![GAME_SESSION_ID.into()]
+            }
+        )
+        .main_failed());
+    let init = GameSessionInit {
+        wordle_program: WORDLE_ID.into(),
+        config: SessionConfig::default(),
+        import: None,
+    };
+    assert!(!game_session.send(PLAYER, init).main_failed());
+}
+
+/// The event game-session replied to the player.
+fn reply(result: &RunResult) -> Result<SessionEvent, String> {
+    result
+        .log()
+        .iter()
+        .filter(|log| log.destination() == PLAYER.into())
+        .find_map(|log| SessionEvent::decode(&mut log.payload()).ok())
+        .ok_or("no reply from game-session".to_string())
+}
+
+/// Plays one game, returns the number of guesses and the result.
+fn play(game_session: &Program, args: &Args) -> Result<(u8, SessionResult), String> {
+    let game_id = match reply(&game_session.send(
+        PLAYER,
+        SessionAction::StartGame {
+            bank: args.bank.clone(),
+        },
+    ))? {
+        SessionEvent::GameStarted { game_id, .. } => game_id,
+        event => return Err(format!("unexpected reply to StartGame: {event:?}")),
+    };
+
+    let mut solver = Solver::new(args.words.iter().cloned());
+    let mut attempts = 0;
+    loop {
+        let guess = solver
+            .suggest(args.strategy)
+            .ok_or("no candidate left, the word list does not match the bank")?;
+        attempts += 1;
+        match reply(&game_session.send(
+            PLAYER,
+            SessionAction::CheckWord {
+                game_id,
+                word: guess.clone(),
+            },
+        ))? {
+            SessionEvent::WordChecked {
+                correct_positions,
+                contained_in_word,
+                ..
+            } => {
+                solver.record(
+                    guess,
+                    Feedback {
+                        correct_positions,
+                        contained_in_word,
+                    },
+                );
+            }
+            SessionEvent::GameOver { result, .. } => return Ok((attempts, result)),
+            event => return Err(format!("unexpected reply to CheckWord: {event:?}")),
+        }
+    }
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|error| {
+        eprintln!("{error}\n{USAGE}");
+        process::exit(2);
+    });
+
+    let sys = System::new();
+    deploy(&sys, &args.wasm_dir);
+    let game_session = sys
+        .get_program(GAME_SESSION_ID)
+        .expect("game-session is not deployed");
+
+    let mut solved = Vec::new();
+    let mut lost = 0;
+    for game in 0..args.games {
+        match play(&game_session, &args) {
+            Ok((attempts, SessionResult::Win)) => solved.push(attempts),
+            Ok(_) => lost += 1,
+            Err(error) => {
+                eprintln!("game {game}: {error}");
+                process::exit(1);
+            }
+        }
+    }
+
+    println!(
+        "games: {}, solved: {}, lost: {}",
+        args.games,
+        solved.len(),
+        lost
+    );
+    if !solved.is_empty() {
+        let average =
+            solved.iter().map(|attempts| *attempts as f64).sum::<f64>() / solved.len() as f64;
+        println!("average solve length: {average:.2}");
+        for attempts in 1..=wordle_engine::MAX_ATTEMPTS {
+            let count = solved.iter().filter(|solved| **solved == attempts).count();
+            println!("{attempts}: {count}");
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Off-chain assistant built on the game rules of `wordle-engine`: keeps the words that
+//! still fit the feedback of the guesses made so far and ranks the next guess.
+//!
+//! Words are compared as given, so they are expected in their normalized form, as replied
+//! by the programs (see `wordle_io::Alphabet::normalize`).
+
+use std::collections::HashMap;
+
+use wordle_engine::{score, Feedback, WORD_LENGTH};
+
+/// What a guess tells about one of its letters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LetterHint {
+    Absent,
+    Present,
+    Correct,
+}
+
+/// Feedback as one hint per letter of the guess, the key candidates are grouped by.
+pub type Pattern = [LetterHint; WORD_LENGTH];
+
+pub fn pattern(feedback: &Feedback) -> Pattern {
+    let mut pattern = [LetterHint::Absent; WORD_LENGTH];
+    for i in &feedback.contained_in_word {
+        pattern[*i as usize] = LetterHint::Present;
+    }
+    for i in &feedback.correct_positions {
+        pattern[*i as usize] = LetterHint::Correct;
+    }
+    pattern
+}
+
+/// Whether `candidate` being the answer would have produced `feedback` for `guess`.
+pub fn is_consistent(candidate: &str, guess: &str, feedback: &Feedback) -> bool {
+    score(candidate, guess).is_ok_and(|expected| pattern(&expected) == pattern(feedback))
+}
+
+/// Words that fit the feedback of every guess in `history`.
+pub fn candidates<'a>(words: &'a [String], history: &[(String, Feedback)]) -> Vec<&'a str> {
+    words
+        .iter()
+        .filter(|word| {
+            history
+                .iter()
+                .all(|(guess, feedback)| is_consistent(word, guess, feedback))
+        })
+        .map(String::as_str)
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Highest expected information, in bits, over the remaining candidates.
+    Entropy,
+    /// Smallest number of candidates left in the worst case.
+    Minimax,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankedGuess {
+    pub word: String,
+    /// Bits of information for `Strategy::Entropy`, worst case candidates left for
+    /// `Strategy::Minimax`.
+    pub score: f64,
+    /// Whether the guess is one of the candidates, so it may win right away.
+    pub candidate: bool,
+}
+
+/// Number of candidates left for each feedback `guess` can get.
+fn partition(guess: &str, candidates: &[&str]) -> HashMap<Pattern, usize> {
+    let mut buckets = HashMap::new();
+    for candidate in candidates {
+        if let Ok(feedback) = score(candidate, guess) {
+            *buckets.entry(pattern(&feedback)).or_insert(0) += 1;
+        }
+    }
+    buckets
+}
+
+/// Ranks `guesses` best first. Equal scores prefer candidates, then alphabetical order,
+/// so the ranking is deterministic.
+pub fn rank(guesses: &[String], candidates: &[&str], strategy: Strategy) -> Vec<RankedGuess> {
+    let total = candidates.len() as f64;
+    let mut ranked: Vec<RankedGuess> = guesses
+        .iter()
+        .map(|guess| {
+            let buckets = partition(guess, candidates);
+            let score = match strategy {
+                Strategy::Entropy => buckets
+                    .values()
+                    .map(|count| {
+                        let p = *count as f64 / total;
+                        -p * p.log2()
+                    })
+                    .sum(),
+                Strategy::Minimax => buckets.values().copied().max().unwrap_or(0) as f64,
+            };
+            RankedGuess {
+                word: guess.clone(),
+                score,
+                candidate: candidates.contains(&guess.as_str()),
+            }
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| {
+        let by_score = match strategy {
+            Strategy::Entropy => b.score.total_cmp(&a.score),
+            Strategy::Minimax => a.score.total_cmp(&b.score),
+        };
+        by_score
+            .then(b.candidate.cmp(&a.candidate))
+            .then(a.word.cmp(&b.word))
+    });
+    ranked
+}
+
+/// Candidates and suggestions for one game, given the words of its bank.
+#[derive(Debug, Clone, Default)]
+pub struct Solver {
+    words: Vec<String>,
+    history: Vec<(String, Feedback)>,
+}
+
+impl Solver {
+    pub fn new<I, W>(words: I) -> Self
+    where
+        I: IntoIterator<Item = W>,
+        W: Into<String>,
+    {
+        let mut words: Vec<String> = words.into_iter().map(Into::into).collect();
+        words.sort();
+        words.dedup();
+        Self {
+            words,
+            history: Vec::new(),
+        }
+    }
+
+    /// Records the feedback replied for `guess`.
+    pub fn record(&mut self, guess: impl Into<String>, feedback: Feedback) {
+        self.history.push((guess.into(), feedback));
+    }
+
+    pub fn history(&self) -> &[(String, Feedback)] {
+        &self.history
+    }
+
+    pub fn candidates(&self) -> Vec<&str> {
+        candidates(&self.words, &self.history)
+    }
+
+    /// Every word of the bank, ranked as the next guess.
+    pub fn rank(&self, strategy: Strategy) -> Vec<RankedGuess> {
+        let candidates = self.candidates();
+        match candidates.len() {
+            0 => Vec::new(),
+            // nothing left to learn, the only candidate is the answer
+            1 => vec![RankedGuess {
+                word: candidates[0].to_string(),
+                score: 0.0,
+                candidate: true,
+            }],
+            _ => rank(&self.words, &candidates, strategy),
+        }
+    }
+
+    pub fn suggest(&self, strategy: Strategy) -> Option<String> {
+        self.rank(strategy)
+            .into_iter()
+            .next()
+            .map(|ranked| ranked.word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: [&str; 8] = [
+        "house", "human", "horse", "hours", "mouse", "shore", "horns", "sound",
+    ];
+
+    fn words() -> Vec<String> {
+        WORDS.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn patterns_follow_feedback() {
+        use LetterHint::*;
+        let feedback = score("house", "human").unwrap();
+        assert_eq!(
+            pattern(&feedback),
+            [Correct, Present, Absent, Absent, Absent]
+        );
+    }
+
+    #[test]
+    fn filters_candidates() {
+        let words = words();
+        let history = vec![("human".to_string(), score("house", "human").unwrap())];
+        // 'h' first, a 'u' elsewhere and no 'm', 'a' or 'n'
+        assert_eq!(candidates(&words, &history), vec!["house", "hours"]);
+
+        let history = vec![("mouse".to_string(), score("house", "mouse").unwrap())];
+        assert_eq!(candidates(&words, &history), vec!["house"]);
+    }
+
+    #[test]
+    fn the_answer_always_stays_a_candidate() {
+        let words = words();
+        for answer in WORDS {
+            let history: Vec<(String, Feedback)> = WORDS
+                .iter()
+                .map(|guess| (guess.to_string(), score(answer, guess).unwrap()))
+                .collect();
+            assert_eq!(candidates(&words, &history), vec![answer]);
+        }
+    }
+
+    #[test]
+    fn ranks_informative_guesses_first() {
+        let words = words();
+        let candidates: Vec<&str> = WORDS.to_vec();
+        for strategy in [Strategy::Entropy, Strategy::Minimax] {
+            let ranked = rank(&words, &candidates, strategy);
+            assert_eq!(ranked.len(), WORDS.len());
+            let best = &ranked[0];
+            let worst = ranked.last().unwrap();
+            match strategy {
+                Strategy::Entropy => assert!(best.score >= worst.score),
+                Strategy::Minimax => assert!(best.score <= worst.score),
+            }
+        }
+    }
+
+    #[test]
+    fn solves_every_answer() {
+        for answer in WORDS {
+            for strategy in [Strategy::Entropy, Strategy::Minimax] {
+                let mut solver = Solver::new(WORDS);
+                let mut attempts = 0;
+                loop {
+                    let guess = solver.suggest(strategy).unwrap();
+                    let feedback = score(answer, &guess).unwrap();
+                    attempts += 1;
+                    if feedback.is_win() {
+                        break;
+                    }
+                    solver.record(guess, feedback);
+                }
+                assert!(
+                    attempts <= wordle_engine::MAX_ATTEMPTS,
+                    "{answer} took {attempts} attempts"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use gstd::prelude::*;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::WordleError;
+
+pub use wordle_engine::WORD_LENGTH;
+
+const LATIN: [char; 26] = [
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
+    't', 'u', 'v', 'w', 'x', 'y', 'z',
+];
+
+const CYRILLIC: [char; 37] = [
+    'а', 'б', 'в', 'г', 'ґ', 'д', 'е', 'є', 'ё', 'ж', 'з', 'и', 'і', 'ї', 'й', 'к', 'л', 'м', 'н',
+    'о', 'п', 'р', 'с', 'т', 'у', 'ф', 'х', 'ц', 'ч', 'ш', 'щ', 'ъ', 'ы', 'ь', 'э', 'ю', 'я',
+];
+
+const LATIN_DIACRITICS: [char; 47] = [
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
+    't', 'u', 'v', 'w', 'x', 'y', 'z', 'à', 'á', 'â', 'ä', 'ç', 'è', 'é', 'ê', 'ë', 'í', 'î', 'ï',
+    'ñ', 'ó', 'ô', 'ö', 'ú', 'û', 'ü', 'ÿ', 'ß',
+];
+
+/// Letters a word bank is written in. Words and guesses are compared after NFC normalization
+/// and lowercasing, so every letter is a single `char`.
+#[derive(Debug, Clone, Copy, Encode, Decode, PartialEq, Eq, TypeInfo)]
+pub enum Alphabet {
+    Latin,
+    Cyrillic,
+    LatinDiacritics,
+}
+
+impl Alphabet {
+    pub fn letters(&self) -> &'static [char] {
+        match self {
+            Alphabet::Latin => &LATIN,
+            Alphabet::Cyrillic => &CYRILLIC,
+            Alphabet::LatinDiacritics => &LATIN_DIACRITICS,
+        }
+    }
+
+    pub fn index_of(&self, letter: char) -> Option<usize> {
+        self.letters().iter().position(|c| *c == letter)
+    }
+
+    /// Lowercases `word`, composes it to NFC and checks that it is `WORD_LENGTH`
+    /// letters of this alphabet long.
+    pub fn normalize(&self, word: &str) -> Result<String, WordleError> {
+        let lowercase: String = word.chars().flat_map(char::to_lowercase).collect();
+        let normalized: String = lowercase.nfc().collect();
+
+        // a grapheme that is still made of several chars after NFC is not a letter of any alphabet
+        let graphemes = normalized.graphemes(true).count();
+        if graphemes != WORD_LENGTH || normalized.chars().count() != graphemes {
+            return Err(WordleError::InvalidWord);
+        }
+        if !normalized.chars().all(|c| self.index_of(c).is_some()) {
+            return Err(WordleError::InvalidWord);
+        }
+        Ok(normalized)
+    }
+}
@@ -1,38 +1,252 @@
 #![no_std]
 
-use gmeta::{InOut, Metadata};
+use gmeta::{In, InOut, Metadata, Out};
 use gstd::{prelude::*, ActorId};
 
+mod alphabet;
+pub use alphabet::*;
+
+/// Bank used when a game does not ask for a specific one.
+pub const DEFAULT_BANK: &str = "en";
+
+/// Version of the `Action`/`Event` protocol. It is bumped whenever an existing action or
+/// event changes shape, additions that can be left out are announced as a `Feature`.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Id of one of a user's games, allocated by the session program. A user can have
+/// several games open at once.
+pub type GameId = u64;
+
 pub struct WordleMetadata;
 
 impl Metadata for WordleMetadata {
-    type Init = ();
+    type Init = In<WordleInit>;
     type Handle = InOut<Action, Event>;
     type Others = ();
     type Reply = ();
     type Signal = ();
-    type State = ();
+    type State = Out<WordleState>;
+}
+
+#[derive(Debug, Default, Clone, Encode, Decode, TypeInfo)]
+pub struct WordleInit {
+    /// Session programs allowed to start and check games on behalf of users.
+    pub session_programs: Vec<ActorId>,
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum Action {
+    /// Starts a game with a random answer of `bank`, or with `word` if it is set and
+    /// is in the bank's dictionary.
     StartGame {
         user: ActorId,
+        game_id: GameId,
+        bank: String,
+        word: Option<String>,
     },
     CheckWord {
         user: ActorId,
+        game_id: GameId,
+        word: String,
+    },
+    EndGame {
+        user: ActorId,
+        game_id: GameId,
+    },
+    /// Reveals the letter of the answer at the first position not in `known`.
+    RevealLetter {
+        user: ActorId,
+        game_id: GameId,
+        known: Vec<u8>,
+    },
+    /// Checks that `word` is in the dictionary of `bank` without starting a game.
+    ValidateWord {
+        bank: String,
         word: String,
     },
+    /// Registers a new bank, e.g. an approved player word pack. Its words are both the
+    /// answers and the dictionary.
+    AddBank {
+        name: String,
+        alphabet: Alphabet,
+        words: Vec<String>,
+    },
+    AddSessionProgram {
+        program: ActorId,
+    },
+    RemoveSessionProgram {
+        program: ActorId,
+    },
+    /// Exports the games from `cursor` on, `limit` of them clamped to `1..=MAX_EXPORT_GAMES`.
+    ExportGames {
+        cursor: u32,
+        limit: u32,
+    },
+    ImportGames {
+        games: GamesChunk,
+    },
+    TransferOwnership {
+        new_owner: ActorId,
+    },
+    Pause,
+    Unpause,
+    /// Asks what the program supports, anyone can send it.
+    GetInfo,
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum Event {
     GameStarted {
         user: ActorId,
+        game_id: GameId,
+        alphabet: Alphabet,
     },
     WordChecked {
         user: ActorId,
+        game_id: GameId,
         correct_positions: Vec<u8>,
         contained_in_word: Vec<u8>,
     },
+    GameEnded {
+        user: ActorId,
+        game_id: GameId,
+    },
+    LetterRevealed {
+        user: ActorId,
+        game_id: GameId,
+        position: u8,
+        /// A single letter, SCALE has no encoding for `char`.
+        letter: String,
+    },
+    WordValidated {
+        bank: String,
+        /// The word in its normalized form.
+        word: String,
+    },
+    BankAdded {
+        name: String,
+    },
+    SessionProgramAdded {
+        program: ActorId,
+    },
+    SessionProgramRemoved {
+        program: ActorId,
+    },
+    GamesExported {
+        games: GamesChunk,
+        next_cursor: Option<u32>,
+    },
+    GamesImported {
+        count: u32,
+    },
+    OwnershipTransferred {
+        new_owner: ActorId,
+    },
+    Paused,
+    Unpaused,
+    Info(WordleInfo),
+    Error(WordleError),
+}
+
+/// Optional parts of the protocol.
+#[derive(Debug, Clone, Copy, Encode, Decode, PartialEq, Eq, TypeInfo)]
+pub enum Feature {
+    /// `Action::ValidateWord`
+    ValidateWord,
+    /// `Action::AddBank`
+    AddBank,
+    /// `Action::ExportGames` and `Action::ImportGames`
+    GameExport,
+    /// `Action::RevealLetter`
+    RevealLetter,
+}
+
+/// Reply to `Action::GetInfo`.
+#[derive(Debug, Default, Clone, Encode, Decode, PartialEq, Eq, TypeInfo)]
+pub struct WordleInfo {
+    pub protocol_version: u32,
+    pub word_lengths: Vec<u8>,
+    pub banks: Vec<String>,
+    pub features: Vec<Feature>,
+}
+
+#[derive(Debug, Clone, Encode, Decode, PartialEq, Eq, TypeInfo)]
+pub enum WordleError {
+    /// The sender is not one of the trusted session programs.
+    UnauthorizedSessionProgram,
+    /// The action can only be sent by the program owner.
+    NotOwner,
+    /// The word is not `WORD_LENGTH` letters of the game's alphabet.
+    InvalidWord,
+    /// There is no word bank with the requested name.
+    UnknownBank,
+    /// The guess is not in the bank's dictionary.
+    NotInDictionary,
+    /// A bank with this name already exists.
+    BankExists,
+    /// A bank needs at least one word and at most 255 answers, all valid in its alphabet.
+    InvalidBank,
+    /// New games can't be started while the program is paused.
+    Paused,
+    /// Every position of the answer is already known.
+    NothingToReveal,
+    /// The user has no game in progress with this id, it was never started or has ended.
+    UnknownGame,
+}
+
+/// Game in progress: the hidden word and the bank it was drawn from.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, Eq, TypeInfo)]
+pub struct Game {
+    pub word: String,
+    pub bank: String,
+}
+
+/// Largest page of games exported by `Action::ExportGames`.
+pub const MAX_EXPORT_GAMES: u32 = 100;
+
+/// Games exported by `Action::ExportGames`, tagged with the schema version.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, Eq, TypeInfo)]
+pub enum GamesChunk {
+    /// Games exported before there were several banks, all of them use `DEFAULT_BANK`.
+    V1(Vec<(ActorId, String)>),
+    /// Games exported before users could have several games, each user's game gets id 0.
+    V2(Vec<(ActorId, Game)>),
+    V3(Vec<((ActorId, GameId), Game)>),
+}
+
+impl GamesChunk {
+    pub fn migrate(self) -> Vec<((ActorId, GameId), Game)> {
+        match self {
+            GamesChunk::V1(games) => games
+                .into_iter()
+                .map(|(user, word)| {
+                    let bank = DEFAULT_BANK.to_string();
+                    ((user, 0), Game { word, bank })
+                })
+                .collect(),
+            GamesChunk::V2(games) => games
+                .into_iter()
+                .map(|(user, game)| ((user, 0), game))
+                .collect(),
+            GamesChunk::V3(games) => games,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode, PartialEq, Eq, TypeInfo)]
+pub struct BankInfo {
+    pub name: String,
+    pub alphabet: Alphabet,
+    /// Number of words a game can be drawn from.
+    pub answers: u32,
+    /// Number of words accepted as guesses, answers included.
+    pub dictionary: u32,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct WordleState {
+    pub owner: ActorId,
+    pub paused: bool,
+    pub session_programs: Vec<ActorId>,
+    pub banks: Vec<BankInfo>,
 }
@@ -1,23 +1,106 @@
 #![no_std]
-use gstd::{collections::HashMap, exec, msg, prelude::*, ActorId};
+use gstd::{
+    collections::{BTreeMap, HashMap, HashSet},
+    exec, msg,
+    prelude::*,
+    ActorId,
+};
 use wordle_io::*;
 
 static mut WORDLE: Option<Wordle> = None;
 
 #[cfg(test)]
-const BANK_OF_WORDS: [&str; 1] = ["house"];
+const EN_ANSWERS: [&str; 1] = ["house"];
 #[cfg(not(test))]
-const BANK_OF_WORDS: [&str; 3] = ["house", "human", "horse"];
+const EN_ANSWERS: [&str; 3] = ["house", "human", "horse"];
+const EN_ALLOWED: [&str; 5] = ["hours", "mouse", "shore", "horns", "sound"];
+
+const DE_ANSWERS: [&str; 3] = ["katze", "blume", "größe"];
+const DE_ALLOWED: [&str; 3] = ["bäume", "mäuse", "küche"];
+
+const UK_ANSWERS: [&str; 3] = ["книга", "школа", "місто"];
+const UK_ALLOWED: [&str; 3] = ["слово", "казка", "пісня"];
+
+struct WordBank {
+    alphabet: Alphabet,
+    answers: Vec<String>,
+    /// Words accepted as guesses in addition to the answers.
+    allowed: Vec<String>,
+}
+
+impl WordBank {
+    fn new(alphabet: Alphabet, answers: &[&str], allowed: &[&str]) -> Self {
+        Self {
+            alphabet,
+            answers: answers.iter().map(|word| word.to_string()).collect(),
+            allowed: allowed.iter().map(|word| word.to_string()).collect(),
+        }
+    }
+
+    /// Normalizes `word` and checks that it is in the dictionary.
+    fn validate(&self, word: &str) -> Result<String, WordleError> {
+        let word = self.alphabet.normalize(word)?;
+        if self.answers.contains(&word) || self.allowed.contains(&word) {
+            Ok(word)
+        } else {
+            Err(WordleError::NotInDictionary)
+        }
+    }
+}
+
+fn default_banks() -> BTreeMap<String, WordBank> {
+    BTreeMap::from([
+        (
+            DEFAULT_BANK.to_string(),
+            WordBank::new(Alphabet::Latin, &EN_ANSWERS, &EN_ALLOWED),
+        ),
+        (
+            "de".to_string(),
+            WordBank::new(Alphabet::LatinDiacritics, &DE_ANSWERS, &DE_ALLOWED),
+        ),
+        (
+            "uk".to_string(),
+            WordBank::new(Alphabet::Cyrillic, &UK_ANSWERS, &UK_ALLOWED),
+        ),
+    ])
+}
 
 #[derive(Default)]
 struct Wordle {
-    games: HashMap<ActorId, String>,
+    owner: ActorId,
+    paused: bool,
+    session_programs: HashSet<ActorId>,
+    banks: BTreeMap<String, WordBank>,
+    games: HashMap<(ActorId, GameId), Game>,
+}
+
+impl Wordle {
+    fn check_session_program(&self) -> Result<(), WordleError> {
+        if self.session_programs.contains(&msg::source()) {
+            Ok(())
+        } else {
+            Err(WordleError::UnauthorizedSessionProgram)
+        }
+    }
+
+    fn check_owner(&self) -> Result<(), WordleError> {
+        if msg::source() == self.owner {
+            Ok(())
+        } else {
+            Err(WordleError::NotOwner)
+        }
+    }
 }
 
 #[no_mangle]
 extern "C" fn init() {
+    let WordleInit { session_programs } = msg::load().expect("Unable to decode init");
     unsafe {
         WORDLE = Some(Wordle {
+            owner: msg::source(),
+            paused: false,
+            session_programs: session_programs.into_iter().collect(),
+            banks: default_banks(),
             games: HashMap::new(),
         });
     }
@@ -28,40 +111,219 @@ extern "C" fn handle() {
     let action: Action = msg::load().expect("Unable to decode ");
     let wordle = unsafe { WORDLE.as_mut().expect("The program is not initialized") };
 
-    let reply = match action {
-        Action::StartGame { user } => {
-            let random_id = get_random_value(BANK_OF_WORDS.len() as u8);
-            let word = BANK_OF_WORDS[random_id as usize];
-            wordle.games.insert(user, word.to_string());
-            Event::GameStarted { user }
-        }
-        Action::CheckWord { user, word } => {
-            if word.len() != 5 {
-                panic!("The length of the word exceeds 5");
+    let reply = match process(wordle, action) {
+        Ok(event) => event,
+        Err(error) => Event::Error(error),
+    };
+
+    msg::reply(reply, 0).expect("Error in sending a reply");
+}
+
+fn process(wordle: &mut Wordle, action: Action) -> Result<Event, WordleError> {
+    let event = match action {
+        Action::StartGame {
+            user,
+            game_id,
+            bank,
+            word,
+        } => {
+            wordle.check_session_program()?;
+            if wordle.paused {
+                return Err(WordleError::Paused);
             }
-            let key_word = wordle
-                .games
-                .get(&user)
-                .expect("There is no game with this user");
-            let mut matched_indices = Vec::with_capacity(5);
-            let mut key_indices = Vec::with_capacity(5);
-            for (i, (a, b)) in key_word.chars().zip(word.chars()).enumerate() {
-                if a == b {
-                    matched_indices.push(i as u8);
-                } else if key_word.contains(b) {
-                    key_indices.push(i as u8);
+            let word_bank = wordle.banks.get(&bank).ok_or(WordleError::UnknownBank)?;
+            let word = match word {
+                Some(word) => word_bank.validate(&word)?,
+                None => {
+                    let random_id = get_random_value(word_bank.answers.len() as u8);
+                    word_bank.answers[random_id as usize].clone()
                 }
+            };
+            let alphabet = word_bank.alphabet;
+            wordle.games.insert((user, game_id), Game { word, bank });
+            Event::GameStarted {
+                user,
+                game_id,
+                alphabet,
+            }
+        }
+        Action::CheckWord {
+            user,
+            game_id,
+            word,
+        } => {
+            wordle.check_session_program()?;
+            let game = wordle
+                .games
+                .get(&(user, game_id))
+                .ok_or(WordleError::UnknownGame)?;
+            let word_bank = wordle
+                .banks
+                .get(&game.bank)
+                .ok_or(WordleError::UnknownBank)?;
+            let word = word_bank.validate(&word)?;
+            let feedback = wordle_engine::score(&game.word, &word)
+                .expect("words are normalized to `WORD_LENGTH` letters");
+
+            // the game is over once the word is guessed, so there is nothing left to keep
+            if feedback.is_win() {
+                wordle.games.remove(&(user, game_id));
             }
 
             Event::WordChecked {
                 user,
-                correct_positions: matched_indices,
-                contained_in_word: key_indices,
+                game_id,
+                correct_positions: feedback.correct_positions,
+                contained_in_word: feedback.contained_in_word,
+            }
+        }
+        Action::EndGame { user, game_id } => {
+            wordle.check_session_program()?;
+            wordle.games.remove(&(user, game_id));
+            Event::GameEnded { user, game_id }
+        }
+        Action::RevealLetter {
+            user,
+            game_id,
+            known,
+        } => {
+            wordle.check_session_program()?;
+            let game = wordle
+                .games
+                .get(&(user, game_id))
+                .ok_or(WordleError::UnknownGame)?;
+            let (position, letter) = game
+                .word
+                .chars()
+                .enumerate()
+                .find(|(position, _)| !known.contains(&(*position as u8)))
+                .ok_or(WordleError::NothingToReveal)?;
+            Event::LetterRevealed {
+                user,
+                game_id,
+                position: position as u8,
+                letter: letter.to_string(),
             }
         }
+        Action::ValidateWord { bank, word } => {
+            wordle.check_session_program()?;
+            let word_bank = wordle.banks.get(&bank).ok_or(WordleError::UnknownBank)?;
+            let word = word_bank.validate(&word)?;
+            Event::WordValidated { bank, word }
+        }
+        Action::AddBank {
+            name,
+            alphabet,
+            words,
+        } => {
+            wordle.check_session_program()?;
+            if wordle.banks.contains_key(&name) {
+                return Err(WordleError::BankExists);
+            }
+            let words = words
+                .iter()
+                .map(|word| alphabet.normalize(word))
+                .collect::<Result<Vec<String>, _>>()
+                .map_err(|_| WordleError::InvalidBank)?;
+            if words.is_empty() || words.len() > u8::MAX as usize {
+                return Err(WordleError::InvalidBank);
+            }
+            wordle.banks.insert(
+                name.clone(),
+                WordBank {
+                    alphabet,
+                    answers: words,
+                    allowed: Vec::new(),
+                },
+            );
+            Event::BankAdded { name }
+        }
+        Action::AddSessionProgram { program } => {
+            wordle.check_owner()?;
+            wordle.session_programs.insert(program);
+            Event::SessionProgramAdded { program }
+        }
+        Action::RemoveSessionProgram { program } => {
+            wordle.check_owner()?;
+            wordle.session_programs.remove(&program);
+            Event::SessionProgramRemoved { program }
+        }
+        Action::ExportGames { cursor, limit } => {
+            wordle.check_owner()?;
+            let mut keys: Vec<(ActorId, GameId)> = wordle.games.keys().copied().collect();
+            keys.sort();
+            let limit = limit.clamp(1, MAX_EXPORT_GAMES) as usize;
+            let games: Vec<((ActorId, GameId), Game)> = keys
+                .iter()
+                .skip(cursor as usize)
+                .take(limit)
+                .map(|key| (*key, wordle.games[key].clone()))
+                .collect();
+            let next = cursor as usize + games.len();
+            Event::GamesExported {
+                games: GamesChunk::V3(games),
+                next_cursor: (next < keys.len()).then_some(next as u32),
+            }
+        }
+        Action::ImportGames { games } => {
+            wordle.check_owner()?;
+            let games = games.migrate();
+            let count = games.len() as u32;
+            wordle.games.extend(games);
+            Event::GamesImported { count }
+        }
+        Action::TransferOwnership { new_owner } => {
+            wordle.check_owner()?;
+            wordle.owner = new_owner;
+            Event::OwnershipTransferred { new_owner }
+        }
+        Action::Pause => {
+            wordle.check_owner()?;
+            wordle.paused = true;
+            Event::Paused
+        }
+        Action::Unpause => {
+            wordle.check_owner()?;
+            wordle.paused = false;
+            Event::Unpaused
+        }
+        Action::GetInfo => Event::Info(WordleInfo {
+            protocol_version: PROTOCOL_VERSION,
+            word_lengths: vec![WORD_LENGTH as u8],
+            banks: wordle.banks.keys().cloned().collect(),
+            features: vec![
+                Feature::ValidateWord,
+                Feature::AddBank,
+                Feature::GameExport,
+                Feature::RevealLetter,
+            ],
+        }),
     };
 
-    msg::reply(reply, 0).expect("Error in sending a reply");
+    Ok(event)
+}
+
+#[no_mangle]
+extern "C" fn state() {
+    let wordle = unsafe { WORDLE.take().expect("The program is not initialized") };
+    // hidden words stay private, only the bank sizes are exposed
+    let banks = wordle
+        .banks
+        .iter()
+        .map(|(name, bank)| BankInfo {
+            name: name.clone(),
+            alphabet: bank.alphabet,
+            answers: bank.answers.len() as u32,
+            dictionary: (bank.answers.len() + bank.allowed.len()) as u32,
+        })
+        .collect();
+    let state = WordleState {
+        owner: wordle.owner,
+        paused: wordle.paused,
+        session_programs: wordle.session_programs.into_iter().collect(),
+        banks,
+    };
+    msg::reply(state, 0).expect("Failed to encode or reply with `WordleState` from `state()`");
 }
 
 static mut SEED: u8 = 0;
@@ -73,4 +335,4 @@ pub fn get_random_value(range: u8) -> u8 {
     random_input[0] = random_input[0].wrapping_add(seed);
     let (random, _) = exec::random(random_input).expect("Error in getting random number");
     random[0] % range
-}
\ No newline at end of file
+}